@@ -0,0 +1,553 @@
+use crate::{NodeRegistry, SetMiniblockArray, StreamEventType, StreamState, StreamsRegistry};
+use alloy::primitives::{Address, Bytes, FixedBytes};
+use alloy::rpc::types::Log;
+use alloy::sol_types::{SolEvent, SolType};
+
+/// Signatures of the events that can be about a stream, in the unified and legacy event model.
+/// None of them, not even the legacy per-stream events, has the stream id as indexed topic. An
+/// RPC node can only filter on the signature, per stream filtering has to be done on the decoded
+/// events.
+pub const STREAM_EVENT_SIGNATURES: [FixedBytes<32>; 6] = [
+    StreamsRegistry::StreamUpdated::SIGNATURE_HASH,
+    StreamsRegistry::StreamAllocated::SIGNATURE_HASH,
+    StreamsRegistry::StreamCreated::SIGNATURE_HASH,
+    StreamsRegistry::StreamPlacementUpdated::SIGNATURE_HASH,
+    StreamsRegistry::StreamLastMiniblockUpdated::SIGNATURE_HASH,
+    StreamsRegistry::StreamLastMiniblockUpdateFailed::SIGNATURE_HASH,
+];
+
+/// Signature of the event for a rejected miniblock registration, it's only emitted in the legacy
+/// event model.
+pub const MINIBLOCK_UPDATE_FAILED_SIGNATURE: FixedBytes<32> =
+    StreamsRegistry::StreamLastMiniblockUpdateFailed::SIGNATURE_HASH;
+
+/// Miniblock registration for a single stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MiniblockUpdate {
+    pub stream_id: FixedBytes<32>,
+    pub last_miniblock_hash: FixedBytes<32>,
+    pub last_miniblock_num: u64,
+    pub is_sealed: bool,
+}
+
+/// Event emitted by the stream or node registry. Events from the unified `StreamUpdated` model
+/// and the legacy per event model are decoded into the same variants where they carry the same
+/// information.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryEvent {
+    /// Stream allocated, the genesis miniblock is only included in legacy events.
+    Allocated {
+        stream_id: FixedBytes<32>,
+        nodes: Vec<Address>,
+        replication_factor: u64,
+        genesis_hash: FixedBytes<32>,
+        genesis_miniblock: Option<Bytes>,
+    },
+    /// Existing stream added to the registry.
+    Created {
+        stream_id: FixedBytes<32>,
+        nodes: Vec<Address>,
+        replication_factor: u64,
+        genesis_hash: FixedBytes<32>,
+    },
+    /// Stream placed on a new set of nodes.
+    PlacementUpdated {
+        stream_id: FixedBytes<32>,
+        nodes: Vec<Address>,
+        replication_factor: u64,
+    },
+    /// Legacy event for a single node added to or removed from a stream.
+    NodePlacementUpdated {
+        stream_id: FixedBytes<32>,
+        node: Address,
+        is_added: bool,
+    },
+    /// One or more streams got a new last miniblock.
+    MiniblockBatch(Vec<MiniblockUpdate>),
+    /// Miniblock registration rejected by the registry.
+    MiniblockUpdateFailed {
+        stream_id: FixedBytes<32>,
+        last_miniblock_hash: FixedBytes<32>,
+        last_miniblock_num: u64,
+        reason: String,
+    },
+    NodeAdded {
+        node: Address,
+        operator: Address,
+        url: String,
+        status: u8,
+    },
+    NodeRemoved {
+        node: Address,
+    },
+    NodeStatusUpdated {
+        node: Address,
+        status: u8,
+    },
+    NodeUrlUpdated {
+        node: Address,
+        url: String,
+    },
+    /// Log with a registry event signature whose data couldn't be decoded.
+    Undecodable {
+        signature: FixedBytes<32>,
+        reason: String,
+    },
+}
+
+impl RegistryEvent {
+    /// Decode a registry event from raw log data. Returns `None` for logs that are not emitted
+    /// by the registries or have an unknown stream event type. Logs with a registry event
+    /// signature and malformed data are returned as [`RegistryEvent::Undecodable`], so a single
+    /// bad log doesn't end a scan.
+    pub fn decode(log: &alloy::primitives::LogData) -> Option<Self> {
+        let topic0 = *log.topics().first()?;
+        Self::try_decode(topic0, log).unwrap_or_else(|err| {
+            Some(RegistryEvent::Undecodable {
+                signature: topic0,
+                reason: err.to_string(),
+            })
+        })
+    }
+
+    fn try_decode(
+        topic0: FixedBytes<32>,
+        log: &alloy::primitives::LogData,
+    ) -> Result<Option<Self>, alloy::sol_types::Error> {
+        let event = match topic0 {
+            StreamsRegistry::StreamUpdated::SIGNATURE_HASH => {
+                let event = StreamsRegistry::StreamUpdated::decode_log_data(log)?;
+                return Self::decode_stream_updated(event.eventType, &event.data);
+            }
+            StreamsRegistry::StreamAllocated::SIGNATURE_HASH => {
+                let event = StreamsRegistry::StreamAllocated::decode_log_data(log)?;
+                RegistryEvent::Allocated {
+                    stream_id: event.streamId,
+                    nodes: event.nodes,
+                    // legacy streams predate replicated streams
+                    replication_factor: 1,
+                    genesis_hash: event.genesisMiniblockHash,
+                    genesis_miniblock: Some(event.genesisMiniblock),
+                }
+            }
+            StreamsRegistry::StreamCreated::SIGNATURE_HASH => {
+                let event = StreamsRegistry::StreamCreated::decode_log_data(log)?;
+                RegistryEvent::Created {
+                    replication_factor: event.stream.replication_factor(),
+                    stream_id: event.streamId,
+                    nodes: event.stream.nodes,
+                    genesis_hash: event.genesisMiniblockHash,
+                }
+            }
+            StreamsRegistry::StreamPlacementUpdated::SIGNATURE_HASH => {
+                let event = StreamsRegistry::StreamPlacementUpdated::decode_log_data(log)?;
+                RegistryEvent::NodePlacementUpdated {
+                    stream_id: event.streamId,
+                    node: event.nodeAddress,
+                    is_added: event.isAdded,
+                }
+            }
+            StreamsRegistry::StreamLastMiniblockUpdated::SIGNATURE_HASH => {
+                let event = StreamsRegistry::StreamLastMiniblockUpdated::decode_log_data(log)?;
+                RegistryEvent::MiniblockBatch(vec![MiniblockUpdate {
+                    stream_id: event.streamId,
+                    last_miniblock_hash: event.lastMiniblockHash,
+                    last_miniblock_num: event.lastMiniblockNum,
+                    is_sealed: event.isSealed,
+                }])
+            }
+            StreamsRegistry::StreamLastMiniblockUpdateFailed::SIGNATURE_HASH => {
+                let event = StreamsRegistry::StreamLastMiniblockUpdateFailed::decode_log_data(log)?;
+                RegistryEvent::MiniblockUpdateFailed {
+                    stream_id: event.streamId,
+                    last_miniblock_hash: event.lastMiniblockHash,
+                    last_miniblock_num: event.lastMiniblockNum,
+                    reason: event.reason,
+                }
+            }
+            NodeRegistry::NodeAdded::SIGNATURE_HASH => {
+                let event = NodeRegistry::NodeAdded::decode_log_data(log)?;
+                RegistryEvent::NodeAdded {
+                    node: event.nodeAddress,
+                    operator: event.operator,
+                    url: event.url,
+                    status: event.status,
+                }
+            }
+            NodeRegistry::NodeRemoved::SIGNATURE_HASH => {
+                let event = NodeRegistry::NodeRemoved::decode_log_data(log)?;
+                RegistryEvent::NodeRemoved {
+                    node: event.nodeAddress,
+                }
+            }
+            NodeRegistry::NodeStatusUpdated::SIGNATURE_HASH => {
+                let event = NodeRegistry::NodeStatusUpdated::decode_log_data(log)?;
+                RegistryEvent::NodeStatusUpdated {
+                    node: event.nodeAddress,
+                    status: event.status,
+                }
+            }
+            NodeRegistry::NodeUrlUpdated::SIGNATURE_HASH => {
+                let event = NodeRegistry::NodeUrlUpdated::decode_log_data(log)?;
+                RegistryEvent::NodeUrlUpdated {
+                    node: event.nodeAddress,
+                    url: event.url,
+                }
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(event))
+    }
+
+    fn decode_stream_updated(
+        event_type: u8,
+        data: &[u8],
+    ) -> Result<Option<Self>, alloy::sol_types::Error> {
+        let Ok(event_type) = StreamEventType::try_from(event_type) else {
+            return Ok(None);
+        };
+
+        let event = match event_type {
+            StreamEventType::Allocate => {
+                let state = StreamState::abi_decode_params(data)?;
+                RegistryEvent::Allocated {
+                    replication_factor: state.replication_factor(),
+                    stream_id: state.streamId,
+                    nodes: state.stream.nodes,
+                    genesis_hash: state.stream.lastMiniblockHash,
+                    genesis_miniblock: None,
+                }
+            }
+            StreamEventType::Create => {
+                let state = StreamState::abi_decode_params(data)?;
+                RegistryEvent::Created {
+                    replication_factor: state.replication_factor(),
+                    stream_id: state.streamId,
+                    nodes: state.stream.nodes,
+                    genesis_hash: state.stream.lastMiniblockHash,
+                }
+            }
+            StreamEventType::PlacementUpdated => {
+                let state = StreamState::abi_decode_params(data)?;
+                RegistryEvent::PlacementUpdated {
+                    replication_factor: state.replication_factor(),
+                    stream_id: state.streamId,
+                    nodes: state.stream.nodes,
+                }
+            }
+            StreamEventType::LastMiniblockBatchUpdated => RegistryEvent::MiniblockBatch(
+                SetMiniblockArray::abi_decode_params(data)?
+                    .into_iter()
+                    .map(|mb| MiniblockUpdate {
+                        stream_id: mb.streamId,
+                        last_miniblock_hash: mb.lastMiniblockHash,
+                        last_miniblock_num: mb.lastMiniblockNum,
+                        is_sealed: mb.isSealed,
+                    })
+                    .collect(),
+            ),
+            _ => return Ok(None),
+        };
+
+        Ok(Some(event))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RegistryEvent::Allocated { .. } => "StreamAllocated",
+            RegistryEvent::Created { .. } => "StreamCreated",
+            RegistryEvent::PlacementUpdated { .. } => "PlacementUpdate",
+            RegistryEvent::NodePlacementUpdated { .. } => "NodePlacementUpdate",
+            RegistryEvent::MiniblockBatch(_) => "MiniblockUpdated",
+            RegistryEvent::MiniblockUpdateFailed { .. } => "MiniblockUpdateFailed",
+            RegistryEvent::NodeAdded { .. } => "NodeAdded",
+            RegistryEvent::NodeRemoved { .. } => "NodeRemoved",
+            RegistryEvent::NodeStatusUpdated { .. } => "NodeStatusUpdated",
+            RegistryEvent::NodeUrlUpdated { .. } => "NodeUrlUpdated",
+            RegistryEvent::Undecodable { .. } => "Undecodable",
+        }
+    }
+
+    /// Streams the event is about, empty for node events.
+    pub fn stream_ids(&self) -> Vec<FixedBytes<32>> {
+        match self {
+            RegistryEvent::MiniblockBatch(updates) => {
+                updates.iter().map(|update| update.stream_id).collect()
+            }
+            RegistryEvent::Allocated { stream_id, .. }
+            | RegistryEvent::Created { stream_id, .. }
+            | RegistryEvent::PlacementUpdated { stream_id, .. }
+            | RegistryEvent::NodePlacementUpdated { stream_id, .. }
+            | RegistryEvent::MiniblockUpdateFailed { stream_id, .. } => vec![*stream_id],
+            RegistryEvent::NodeAdded { .. }
+            | RegistryEvent::NodeRemoved { .. }
+            | RegistryEvent::NodeStatusUpdated { .. }
+            | RegistryEvent::NodeUrlUpdated { .. }
+            | RegistryEvent::Undecodable { .. } => vec![],
+        }
+    }
+
+    /// Returns true if the event is about the given stream.
+    pub fn is_for_stream(&self, id: &FixedBytes<32>) -> bool {
+        match self {
+            RegistryEvent::Allocated { stream_id, .. }
+            | RegistryEvent::Created { stream_id, .. }
+            | RegistryEvent::PlacementUpdated { stream_id, .. }
+            | RegistryEvent::NodePlacementUpdated { stream_id, .. }
+            | RegistryEvent::MiniblockUpdateFailed { stream_id, .. } => stream_id == id,
+            RegistryEvent::MiniblockBatch(updates) => {
+                updates.iter().any(|update| update.stream_id == *id)
+            }
+            RegistryEvent::NodeAdded { .. }
+            | RegistryEvent::NodeRemoved { .. }
+            | RegistryEvent::NodeStatusUpdated { .. }
+            | RegistryEvent::NodeUrlUpdated { .. }
+            | RegistryEvent::Undecodable { .. } => false,
+        }
+    }
+
+    /// Returns the event restricted to the streams for which `keep` returns true, miniblock
+    /// batches only keep the updates for these streams. Node events and undecodable logs are
+    /// never kept.
+    pub fn filter_streams(&self, keep: impl Fn(&FixedBytes<32>) -> bool) -> Option<Self> {
+        match self {
+            RegistryEvent::MiniblockBatch(updates) => {
+                let updates: Vec<_> = updates
+                    .iter()
+                    .filter(|update| keep(&update.stream_id))
+                    .cloned()
+                    .collect();
+                (!updates.is_empty()).then_some(RegistryEvent::MiniblockBatch(updates))
+            }
+            RegistryEvent::Allocated { stream_id, .. }
+            | RegistryEvent::Created { stream_id, .. }
+            | RegistryEvent::PlacementUpdated { stream_id, .. }
+            | RegistryEvent::NodePlacementUpdated { stream_id, .. }
+            | RegistryEvent::MiniblockUpdateFailed { stream_id, .. } => {
+                keep(stream_id).then(|| self.clone())
+            }
+            RegistryEvent::NodeAdded { .. }
+            | RegistryEvent::NodeRemoved { .. }
+            | RegistryEvent::NodeStatusUpdated { .. }
+            | RegistryEvent::NodeUrlUpdated { .. }
+            | RegistryEvent::Undecodable { .. } => None,
+        }
+    }
+
+    /// Returns the event restricted to the given stream, see [`RegistryEvent::filter_streams`].
+    pub fn for_stream(&self, id: &FixedBytes<32>) -> Option<Self> {
+        self.filter_streams(|stream_id| stream_id == id)
+    }
+
+    /// Returns true if the event is about the given node or places a stream on it. Miniblock
+    /// updates and failures don't carry the placement, callers that need them must track which
+    /// streams are placed on the node.
+    pub fn involves_node(&self, node: &Address) -> bool {
+        match self {
+            RegistryEvent::Allocated { nodes, .. }
+            | RegistryEvent::Created { nodes, .. }
+            | RegistryEvent::PlacementUpdated { nodes, .. } => nodes.contains(node),
+            RegistryEvent::NodePlacementUpdated { node: n, .. }
+            | RegistryEvent::NodeAdded { node: n, .. }
+            | RegistryEvent::NodeRemoved { node: n }
+            | RegistryEvent::NodeStatusUpdated { node: n, .. }
+            | RegistryEvent::NodeUrlUpdated { node: n, .. } => n == node,
+            RegistryEvent::MiniblockBatch(_)
+            | RegistryEvent::MiniblockUpdateFailed { .. }
+            | RegistryEvent::Undecodable { .. } => false,
+        }
+    }
+}
+
+/// Registry event together with the location of the log that emitted it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistryLog {
+    pub block_number: u64,
+    pub block_hash: FixedBytes<32>,
+    pub transaction_hash: FixedBytes<32>,
+    pub log_index: u64,
+    pub event: RegistryEvent,
+}
+
+impl RegistryLog {
+    /// Decode a registry log, returns `None` for logs that don't contain a registry event. See
+    /// [`RegistryEvent::decode`].
+    pub fn decode(log: &Log) -> Option<Self> {
+        RegistryEvent::decode(log.data()).map(|event| RegistryLog {
+            block_number: log.block_number.unwrap_or_default(),
+            block_hash: log.block_hash.unwrap_or_default(),
+            transaction_hash: log.transaction_hash.unwrap_or_default(),
+            log_index: log.log_index.unwrap_or_default(),
+            event,
+        })
+    }
+
+    /// Returns the log restricted to the streams for which `keep` returns true, see
+    /// [`RegistryEvent::filter_streams`].
+    pub fn filter_streams(&self, keep: impl Fn(&FixedBytes<32>) -> bool) -> Option<Self> {
+        self.event.filter_streams(keep).map(|event| RegistryLog {
+            event,
+            ..self.clone()
+        })
+    }
+
+    /// Returns the log restricted to the given stream, see [`RegistryEvent::for_stream`].
+    pub fn for_stream(&self, id: &FixedBytes<32>) -> Option<Self> {
+        self.filter_streams(|stream_id| stream_id == id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SetMiniblock, Stream};
+    use alloy::primitives::LogData;
+
+    fn stream_id(last: u8) -> FixedBytes<32> {
+        let mut id = FixedBytes::<32>::ZERO;
+        id[0] = 0x20;
+        id[31] = last;
+        id
+    }
+
+    fn stream_updated(event_type: StreamEventType, data: Vec<u8>) -> LogData {
+        LogData::from(&StreamsRegistry::StreamUpdated {
+            eventType: event_type as u8,
+            data: data.into(),
+        })
+    }
+
+    #[test]
+    fn decode_miniblock_batch() {
+        let data = SetMiniblockArray::abi_encode_params(&vec![
+            SetMiniblock {
+                streamId: stream_id(1),
+                prevMiniBlockHash: FixedBytes::ZERO,
+                lastMiniblockHash: FixedBytes::repeat_byte(0xaa),
+                lastMiniblockNum: 7,
+                isSealed: false,
+            },
+            SetMiniblock {
+                streamId: stream_id(2),
+                prevMiniBlockHash: FixedBytes::ZERO,
+                lastMiniblockHash: FixedBytes::repeat_byte(0xbb),
+                lastMiniblockNum: 9,
+                isSealed: true,
+            },
+        ]);
+
+        let event = RegistryEvent::decode(&stream_updated(
+            StreamEventType::LastMiniblockBatchUpdated,
+            data,
+        ))
+        .unwrap();
+
+        assert!(event.is_for_stream(&stream_id(1)));
+        assert_eq!(
+            Some(RegistryEvent::MiniblockBatch(vec![MiniblockUpdate {
+                stream_id: stream_id(2),
+                last_miniblock_hash: FixedBytes::repeat_byte(0xbb),
+                last_miniblock_num: 9,
+                is_sealed: true,
+            }])),
+            event.for_stream(&stream_id(2))
+        );
+        assert_eq!(None, event.for_stream(&stream_id(3)));
+    }
+
+    #[test]
+    fn decode_placement_update() {
+        let node = Address::repeat_byte(0x42);
+        let data = StreamState::abi_encode_params(&StreamState {
+            streamId: stream_id(1),
+            stream: Stream {
+                lastMiniblockHash: FixedBytes::ZERO,
+                lastMiniblockNum: 0,
+                reserved0: 3,
+                flags: 0,
+                nodes: vec![node],
+            },
+        });
+
+        let event = RegistryEvent::decode(&stream_updated(StreamEventType::PlacementUpdated, data));
+
+        assert_eq!(
+            Some(RegistryEvent::PlacementUpdated {
+                stream_id: stream_id(1),
+                nodes: vec![node],
+                replication_factor: 3,
+            }),
+            event
+        );
+    }
+
+    #[test]
+    fn decode_legacy_events() {
+        let log = LogData::from(&StreamsRegistry::StreamLastMiniblockUpdated {
+            streamId: stream_id(1),
+            lastMiniblockHash: FixedBytes::repeat_byte(0xcc),
+            lastMiniblockNum: 12,
+            isSealed: false,
+        });
+        assert_eq!(
+            Some(RegistryEvent::MiniblockBatch(vec![MiniblockUpdate {
+                stream_id: stream_id(1),
+                last_miniblock_hash: FixedBytes::repeat_byte(0xcc),
+                last_miniblock_num: 12,
+                is_sealed: false,
+            }])),
+            RegistryEvent::decode(&log)
+        );
+
+        let node = Address::repeat_byte(0x01);
+        let log = LogData::from(&NodeRegistry::NodeStatusUpdated {
+            nodeAddress: node,
+            status: 2,
+        });
+        assert_eq!(
+            Some(RegistryEvent::NodeStatusUpdated { node, status: 2 }),
+            RegistryEvent::decode(&log)
+        );
+    }
+
+    #[test]
+    fn decode_unrelated_log() {
+        let log = LogData::new_unchecked(vec![FixedBytes::repeat_byte(0x01)], Bytes::new());
+        assert_eq!(None, RegistryEvent::decode(&log));
+    }
+
+    #[test]
+    fn decode_legacy_allocation() {
+        let node = Address::repeat_byte(0x42);
+        let log = LogData::from(&StreamsRegistry::StreamAllocated {
+            streamId: stream_id(1),
+            nodes: vec![node],
+            genesisMiniblockHash: FixedBytes::repeat_byte(0xdd),
+            genesisMiniblock: Bytes::from_static(&[1, 2, 3]),
+        });
+        assert_eq!(
+            Some(RegistryEvent::Allocated {
+                stream_id: stream_id(1),
+                nodes: vec![node],
+                replication_factor: 1,
+                genesis_hash: FixedBytes::repeat_byte(0xdd),
+                genesis_miniblock: Some(Bytes::from_static(&[1, 2, 3])),
+            }),
+            RegistryEvent::decode(&log)
+        );
+    }
+
+    #[test]
+    fn decode_invalid_event_data() {
+        let log = stream_updated(StreamEventType::Allocate, vec![0x01]);
+        let event = RegistryEvent::decode(&log).unwrap();
+        assert!(matches!(
+            event,
+            RegistryEvent::Undecodable { signature, .. }
+                if signature == StreamsRegistry::StreamUpdated::SIGNATURE_HASH
+        ));
+        assert!(event.stream_ids().is_empty());
+        assert_eq!(None, event.for_stream(&stream_id(1)));
+    }
+}
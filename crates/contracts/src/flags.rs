@@ -0,0 +1,177 @@
+use crate::{Stream, StreamState, StreamsRegistry};
+use std::fmt;
+
+/// Decoded `flags` field of a stream record in the stream registry.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct StreamFlags(u64);
+
+impl StreamFlags {
+    /// The stream is sealed, the registry rejects new miniblocks for it.
+    pub const SEALED: u64 = 1;
+
+    /// All bits that have a known meaning.
+    pub const KNOWN: u64 = Self::SEALED;
+
+    pub const fn new(bits: u64) -> Self {
+        StreamFlags(bits)
+    }
+
+    /// Raw value as stored in the registry.
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+
+    pub const fn is_sealed(&self) -> bool {
+        self.0 & Self::SEALED != 0
+    }
+
+    /// Bits that are set but don't have a known meaning.
+    pub const fn unknown(&self) -> u64 {
+        self.0 & !Self::KNOWN
+    }
+}
+
+impl fmt::Display for StreamFlags {
+    /// Lists the names of the set flags, e.g. `sealed`, unknown bits are printed in hex.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names = Vec::new();
+        if self.is_sealed() {
+            names.push("sealed".to_string());
+        }
+        if self.unknown() != 0 {
+            names.push(format!("unknown {:#x}", self.unknown()));
+        }
+        if names.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", names.join(", "))
+        }
+    }
+}
+
+/// Decoded `reserved0` field of a stream record in the stream registry. The low byte holds the
+/// replication factor, the other bits are unused.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct StreamMeta(u64);
+
+impl StreamMeta {
+    /// Bits that hold the replication factor.
+    pub const REPLICATION_FACTOR_MASK: u64 = 0xFF;
+
+    /// All bits that have a known meaning.
+    pub const KNOWN: u64 = Self::REPLICATION_FACTOR_MASK;
+
+    pub const fn new(bits: u64) -> Self {
+        StreamMeta(bits)
+    }
+
+    /// Raw value as stored in the registry.
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Number of nodes that participate in the stream quorum.
+    pub const fn replication_factor(&self) -> u64 {
+        match self.0 & Self::REPLICATION_FACTOR_MASK {
+            0 => 1, // backwards compatibility before replicated streams were introduced
+            repl_factor => repl_factor,
+        }
+    }
+
+    /// False for streams that were allocated before replicated streams were introduced and have
+    /// an implicit replication factor of 1.
+    pub const fn has_replication_factor(&self) -> bool {
+        self.0 & Self::REPLICATION_FACTOR_MASK != 0
+    }
+
+    /// Bits that are set but don't have a known meaning.
+    pub const fn unknown(&self) -> u64 {
+        self.0 & !Self::KNOWN
+    }
+}
+
+impl fmt::Display for StreamMeta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "repl factor {}", self.replication_factor())?;
+        if !self.has_replication_factor() {
+            write!(f, " (implicit)")?;
+        }
+        if self.unknown() != 0 {
+            write!(f, ", unknown {:#x}", self.unknown())?;
+        }
+        Ok(())
+    }
+}
+
+impl StreamsRegistry::Stream {
+    pub fn stream_flags(&self) -> StreamFlags {
+        StreamFlags::new(self.flags)
+    }
+
+    pub fn meta(&self) -> StreamMeta {
+        StreamMeta::new(self.reserved0)
+    }
+
+    pub fn is_sealed(&self) -> bool {
+        self.stream_flags().is_sealed()
+    }
+
+    pub fn replication_factor(&self) -> u64 {
+        self.meta().replication_factor()
+    }
+}
+
+impl Stream {
+    pub fn stream_flags(&self) -> StreamFlags {
+        StreamFlags::new(self.flags)
+    }
+
+    pub fn meta(&self) -> StreamMeta {
+        StreamMeta::new(self.reserved0)
+    }
+}
+
+impl StreamState {
+    pub fn stream_flags(&self) -> StreamFlags {
+        self.stream.stream_flags()
+    }
+
+    pub fn meta(&self) -> StreamMeta {
+        self.stream.meta()
+    }
+
+    pub fn is_sealed(&self) -> bool {
+        self.stream_flags().is_sealed()
+    }
+
+    pub fn replication_factor(&self) -> u64 {
+        self.meta().replication_factor()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_stream_flags_and_meta() {
+        let flags = StreamFlags::new(0);
+        assert!(!flags.is_sealed());
+        assert_eq!("none", flags.to_string());
+
+        let flags = StreamFlags::new(StreamFlags::SEALED | 0x10);
+        assert!(flags.is_sealed());
+        assert_eq!(0x10, flags.unknown());
+        assert_eq!("sealed, unknown 0x10", flags.to_string());
+
+        let meta = StreamMeta::new(0);
+        assert_eq!(1, meta.replication_factor());
+        assert!(!meta.has_replication_factor());
+        assert_eq!("repl factor 1 (implicit)", meta.to_string());
+
+        let meta = StreamMeta::new(0x0100 | 3);
+        assert_eq!(3, meta.replication_factor());
+        assert_eq!(0x0100, meta.unknown());
+        assert_eq!("repl factor 3, unknown 0x100", meta.to_string());
+    }
+}
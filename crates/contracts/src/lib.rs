@@ -1,8 +1,14 @@
+mod events;
+mod flags;
+
+pub use events::*;
+pub use flags::*;
+
 use alloy::sol;
 
 sol!(
     #[allow(missing_docs)]
-    #[sol(rpc = true, abi = true, all_derives = true, extra_methods = true)]
+    #[sol(rpc = true, abi = true, extra_methods = true)]
     #[derive(Debug)]
     StreamsRegistry,
     "abi/StreamsRegistry.json"
@@ -10,7 +16,7 @@ sol!(
 
 sol!(
     #[allow(missing_docs)]
-    #[sol(rpc = true, abi = true, all_derives = true, extra_methods = true)]
+    #[sol(rpc = true, abi = true, extra_methods = true)]
     #[derive(Debug)]
     NodeRegistry,
     "abi/NodeRegistry.json"
@@ -29,7 +35,7 @@ sol! {
 
     #[allow(missing_docs)]
     #[derive(Debug)]
-    #[sol(rpc=true,abi=true,all_derives=true, extra_methods=true)]
+    #[sol(rpc=true,abi=true,extra_methods=true)]
     struct Stream {
       bytes32 lastMiniblockHash; // 32 bytes, slot 0
       uint64 lastMiniblockNum; // 8 bytes, part of slot 1
@@ -40,7 +46,7 @@ sol! {
 
     #[allow(missing_docs)]
     #[derive(Debug)]
-    #[sol(rpc=true,abi=true,all_derives=true, extra_methods=true)]
+    #[sol(rpc=true,abi=true,extra_methods=true)]
     struct StreamState {
         bytes32 streamId;
         Stream stream;
@@ -48,7 +54,7 @@ sol! {
 
     #[allow(missing_docs)]
     #[derive(Debug)]
-    #[sol(rpc=true,abi=true,all_derives=true,extra_methods=true)]
+    #[sol(rpc=true,abi=true,extra_methods=true)]
     struct SetMiniblock {
         bytes32 streamId;
         bytes32 prevMiniBlockHash;
@@ -59,7 +65,7 @@ sol! {
 
     #[allow(missing_docs)]
     #[derive(Debug)]
-    #[sol(rpc=true,abi=true,all_derives=true, extra_methods=true)]
+    #[sol(rpc=true,abi=true,extra_methods=true)]
     struct StreamAllocated {
         bytes32 streamId;
         address[] nodes;
@@ -71,23 +77,3 @@ sol! {
 pub type SolArrayOf<T> = sol! { T[] };
 
 pub type SetMiniblockArray = SolArrayOf<SetMiniblock>;
-
-impl StreamsRegistry::Stream {
-    pub fn replication_factor(&self) -> u64 {
-        let repl_factor = self.reserved0 & 0xFF;
-        match repl_factor {
-            0 => 1, // backwards compatibility before replicated streams were introduced
-            _ => repl_factor,
-        }
-    }
-}
-
-impl StreamState {
-    pub fn replication_factor(&self) -> u64 {
-        let repl_factor = self.stream.reserved0 & 0xFF;
-        match repl_factor {
-            0 => 1, // backwards compatibility before replicated streams were introduced
-            _ => repl_factor,
-        }
-    }
-}
@@ -1,11 +1,16 @@
-use alloy_primitives::{Address, U256};
+use alloy_primitives::{Address, FixedBytes, U256};
 use alloy_rpc_types::{BlockId, BlockNumberOrTag};
-use crate::{config, stream};
-use alloy_provider::Provider;
+use crate::output::{self, OutputFormat, Report, Table};
+use crate::snapshot::{self, Since, Snapshot};
+use crate::{config, failures, index, miniblock, stream, timeline, watch};
+use chrono::{DateTime, Utc};
 use clap::{Args, Parser, Subcommand, value_parser};
-use towns_protocol_contracts::{NodeRegistry, StreamsRegistry};
-use towns_protocol_types::StreamId;
+use towns_protocol_types::{StreamId, StreamKind, TownsError};
 use eyre::WrapErr;
+use serde::Serialize;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -13,6 +18,39 @@ pub(crate) struct Opts {
     #[arg(short,long,value_enum, default_value_t = config::Network::Omega, env = "TOWNS_GANDALF_NETWORK")]
     pub network: config::Network,
 
+    #[arg(short, long, help = "config file, defaults to ~/.config/gandalf/config.toml", env = "TOWNS_GANDALF_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    #[arg(short, long, help = "named profile from the config file", env = "TOWNS_GANDALF_PROFILE")]
+    pub profile: Option<String>,
+
+    #[arg(long, help = "override the network River chain RPC URL", env = "TOWNS_GANDALF_RPC_URL")]
+    pub rpc_url: Option<String>,
+
+    #[arg(long, help = "override the network registry contract address", value_parser=value_parser!(Address), env = "TOWNS_GANDALF_REGISTRY_ADDRESS")]
+    pub registry_address: Option<Address>,
+
+    #[arg(long, help = "override the river block in which the registry was deployed", env = "TOWNS_GANDALF_DEPLOYMENT_BLOCK")]
+    pub deployment_block: Option<u64>,
+
+    #[arg(long, help = "local registry event index, defaults to ~/.local/share/gandalf/<registry>.sqlite", env = "TOWNS_GANDALF_INDEX")]
+    pub index: Option<PathBuf>,
+
+    #[arg(long, help = "don't read registry events from the local index", env = "TOWNS_GANDALF_NO_INDEX")]
+    pub no_index: bool,
+
+    #[arg(long, help = "river block to query: a block number, latest, finalized or safe", value_parser = value_parser!(Snapshot), env = "TOWNS_GANDALF_BLOCK")]
+    pub block: Option<Snapshot>,
+
+    #[arg(long, visible_alias = "at-time", help = "query the latest river block at this RFC3339 time, e.g. 2025-01-01T00:00:00Z", value_parser = snapshot::parse_time, conflicts_with = "block", env = "TOWNS_GANDALF_AT")]
+    pub at: Option<DateTime<Utc>>,
+
+    #[arg(long, help = "number of get_logs calls that run at the same time during long scans", env = "TOWNS_GANDALF_CONCURRENCY")]
+    pub concurrency: Option<NonZeroUsize>,
+
+    #[arg(short, long, value_enum, global = true, default_value_t = OutputFormat::Table, env = "TOWNS_GANDALF_OUTPUT")]
+    pub output: OutputFormat,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -22,6 +60,7 @@ pub(crate) enum Commands {
     Stream(StreamArgs),
     Miniblock(MiniblockArgs),
     Node(NodeArgs),
+    Index(IndexArgs),
 }
 
 #[derive(Debug, Args)]
@@ -35,16 +74,36 @@ pub(crate) struct StreamArgs {
 }
 
 impl StreamArgs {
-    pub(crate) async fn execute(self, cfg: &config::Config) -> eyre::Result<()> {
+    pub(crate) async fn execute(self, cfg: &config::Config, out: OutputFormat) -> eyre::Result<()> {
         match self.command {
-            StreamCommands::Inception { stream_id } => stream::inception(cfg, stream_id).await,
-            StreamCommands::Details { stream_id, river_block } => stream::details(cfg, stream_id, river_block).await,
-            StreamCommands::Count {} => stream::count(cfg).await,
+            StreamCommands::Inception { stream_id } => output::render(out, &stream::inception(cfg, stream_id).await?),
+            StreamCommands::Details { stream_id, river_block } => output::render(out, &stream::details(cfg, stream_id, river_block).await?),
+            StreamCommands::Count {} => output::render(out, &stream::count(cfg).await?),
+            StreamCommands::List { stream_types, sealed, unsealed, replication_factor, node, page_size, limit } => {
+                let filter = stream::StreamFilter {
+                    stream_types,
+                    sealed: (sealed || unsealed).then_some(sealed),
+                    replication_factor,
+                };
+                output::render(out, &stream::list(cfg, &filter, node, page_size, limit).await?)
+            }
             StreamCommands::Updates {
                 stream_id,
                 scroll_back_river_blocks,
-            } => stream::updates(cfg, stream_id, scroll_back_river_blocks).await,
-            StreamCommands::ActiveStreams { scroll_back_hours, stream_types, hot_duration_hours } => stream::active_streams(cfg, scroll_back_hours, &stream_types, hot_duration_hours).await,
+                since,
+            } => output::render(out, &stream::updates(cfg, stream_id, scroll_back_river_blocks, since).await?),
+            StreamCommands::Timeline { stream_id } => output::render(out, &timeline::timeline(cfg, stream_id).await?),
+            StreamCommands::Failures { since, ongoing, stream_types, by_node } => output::render(out, &failures::failures(cfg, since, ongoing, &stream_types, by_node).await?),
+            StreamCommands::ActiveStreams { scroll_back_hours, stream_types, hot_duration_hours } => output::render(out, &stream::active_streams(cfg, scroll_back_hours, &stream_types, hot_duration_hours).await?),
+            StreamCommands::Watch { stream_ids, stream_types, node, from_block, reorg_depth, poll_interval } => {
+                let opts = watch::WatchOpts {
+                    filter: watch::EventFilter { stream_ids, stream_types, node },
+                    from_block,
+                    reorg_depth,
+                    poll_interval: Duration::from_secs(poll_interval),
+                };
+                watch::watch(cfg, opts, out).await
+            }
         }
     }
 }   
@@ -63,6 +122,23 @@ pub(crate) enum StreamCommands {
     },
     #[command(about = "Print total number of streams")]
     Count {},
+    #[command(about = "List the streams in the registry or on a node, filtered by type, sealed state and replication factor")]
+    List {
+        #[arg(short='t',long,help="the stream kinds to filter by, e.g. channel, dm or 0x20, defaults to all", value_parser=value_parser!(StreamKind))]
+        stream_types: Vec<StreamKind>,
+        #[arg(long,help="only list sealed streams", conflicts_with="unsealed")]
+        sealed: bool,
+        #[arg(long,help="only list streams that are not sealed")]
+        unsealed: bool,
+        #[arg(long,help="only list streams with this replication factor", value_parser=value_parser!(u64))]
+        replication_factor: Option<u64>,
+        #[arg(long,help="only list streams placed on this node", value_parser=value_parser!(Address))]
+        node: Option<Address>,
+        #[arg(long,help="number of streams read from the registry per call", value_parser=value_parser!(u64).range(1..), default_value_t = 1000)]
+        page_size: u64,
+        #[arg(long,help="stop after this many matching streams", value_parser=value_parser!(usize))]
+        limit: Option<usize>,
+    },
 
     #[command(
         about = "Print stream updates in the last n river blocks, if not given defaults to 10000"
@@ -72,15 +148,48 @@ pub(crate) enum StreamCommands {
         stream_id: StreamId,
         #[arg(short,long,help="the number of river blocks to scroll back, defaults to 10000", value_parser=value_parser!(u64), default_value_t = 10000)]
         scroll_back_river_blocks: u64,
+        #[arg(long,help="only print updates since a duration before the river block (e.g. 6h, 2d) or a time (e.g. 2025-01-01T00:00Z)", value_parser=value_parser!(Since), conflicts_with="scroll_back_river_blocks")]
+        since: Option<Since>,
+    },
+    #[command(about = "Print the stream lifecycle from inception to now: placements, miniblocks, failed updates and seal")]
+    Timeline {
+        #[arg(value_parser=value_parser!(StreamId))]
+        stream_id: StreamId,
     },
     #[command(about = "Print the number of streams that got miniblocks in the last n river blocks")]
     ActiveStreams {
         #[arg(short,long,help="the number of hours to scroll back, defaults to 168 (1 week)", value_parser=value_parser!(u64), default_value_t = 168)]
         scroll_back_hours: u64,
-        #[arg(short='t',long,help="the stream types to filter by, defaults to all", value_parser=value_parser!(u8))]
-        stream_types: Vec<u8>,
+        #[arg(short='t',long,help="the stream kinds to filter by, e.g. channel, dm or 0x20, defaults to all", value_parser=value_parser!(StreamKind))]
+        stream_types: Vec<StreamKind>,
         #[arg(short='d',long,help="how many hours before a stream is considered cold (default 4)", value_parser=value_parser!(u64))]
         hot_duration_hours: Vec<u64>,
+    },
+    #[command(about = "Print failed miniblock registrations by reason, stream type and stream")]
+    Failures {
+        #[arg(long,help="only include failures since a duration before the river block (e.g. 6h, 2d) or a time (e.g. 2025-01-01T00:00Z)", value_parser=value_parser!(Since), default_value = "24h")]
+        since: Since,
+        #[arg(long,help="streams that failed since this duration or time and registered no miniblock after the last failure are flagged as ongoing", value_parser=value_parser!(Since), default_value = "1h")]
+        ongoing: Since,
+        #[arg(short='t',long,help="the stream kinds to filter by, e.g. channel, dm or 0x20, defaults to all", value_parser=value_parser!(StreamKind))]
+        stream_types: Vec<StreamKind>,
+        #[arg(long,help="count failures per node that submitted them, costs an RPC call per failure")]
+        by_node: bool,
+    },
+    #[command(about = "Follow registry events as they are mined, stop with ctrl-c")]
+    Watch {
+        #[arg(help="only print events for these streams, defaults to all", value_parser=value_parser!(StreamId))]
+        stream_ids: Vec<StreamId>,
+        #[arg(short='t',long,help="the stream kinds to filter by, e.g. channel, dm or 0x20, defaults to all", value_parser=value_parser!(StreamKind))]
+        stream_types: Vec<StreamKind>,
+        #[arg(long,help="only print events about this node and the streams placed on it", value_parser=value_parser!(Address))]
+        node: Option<Address>,
+        #[arg(short,long,help="river block to start from, defaults to the next block", value_parser=value_parser!(u64))]
+        from_block: Option<u64>,
+        #[arg(long,help="number of recent river blocks that are read again to detect reorgs", value_parser=value_parser!(u64), default_value_t = 12)]
+        reorg_depth: u64,
+        #[arg(long,help="seconds between polls for new blocks when not connected over WebSocket", value_parser=value_parser!(u64), default_value_t = 2)]
+        poll_interval: u64,
     }
 }
 
@@ -95,8 +204,24 @@ pub(crate) struct MiniblockArgs {
 }
 
 impl MiniblockArgs {
-    pub(crate) async fn execute(self, _cfg: &config::Config) -> eyre::Result<()> {
-        todo!()
+    pub(crate) async fn execute(self, cfg: &config::Config, out: OutputFormat) -> eyre::Result<()> {
+        match self.command {
+            MiniblockCommands::Get {
+                stream_id,
+                miniblock_hash,
+                miniblock_num,
+            } => output::render(out, &miniblock::get(cfg, stream_id, miniblock_hash, miniblock_num).await?),
+            MiniblockCommands::Validate {
+                stream_id,
+                from,
+                to,
+                node,
+            } => {
+                let validation = miniblock::validate(cfg, stream_id, from, to, node).await?;
+                output::render(out, &validation)?;
+                validation.into_result()
+            }
+        }
     }
 }
 
@@ -106,7 +231,23 @@ pub(crate) enum MiniblockCommands {
     Get {
         #[arg(value_parser=value_parser!(StreamId))]
         stream_id: StreamId,
-        miniblock_hash: String,
+        #[arg(value_parser=value_parser!(FixedBytes<32>))]
+        miniblock_hash: FixedBytes<32>,
+        #[arg(long, help = "number of the miniblock, it is fetched directly instead of searching the node for the hash", value_parser=value_parser!(u64))]
+        miniblock_num: Option<u64>,
+    },
+    #[command(
+        about = "Validate the miniblock hash chain and compare the node tip with the registry"
+    )]
+    Validate {
+        #[arg(value_parser=value_parser!(StreamId))]
+        stream_id: StreamId,
+        #[arg(long, help = "first miniblock to validate, defaults to the genesis miniblock")]
+        from: Option<u64>,
+        #[arg(long, help = "last miniblock to validate, defaults to the registry tip")]
+        to: Option<u64>,
+        #[arg(long, help = "node to download miniblocks from, defaults to the first stream node")]
+        node: Option<Address>,
     },
 }
 
@@ -123,11 +264,15 @@ pub(crate) struct NodeArgs {
 #[derive(Debug, Subcommand)]
 pub(crate) enum NodeCommands {
     #[command(about = "Print total number of streams on a node")]
-    NodeStreamCount { node_addr: String },
+    NodeStreamCount {
+        #[arg(value_parser=value_parser!(Address))]
+        node_addr: Address,
+    },
     #[command(about = "Print total number of streams on all nodes")]
     AllNodeStreamCount {},
 }
 
+#[derive(Serialize)]
 struct NodeStreamCount {
     address: Address,
     operator: Address,
@@ -136,43 +281,96 @@ struct NodeStreamCount {
     url: String,
 }
 
+#[derive(Serialize)]
+struct NodeStreamCounts {
+    river_block: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_streams: Option<U256>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    incl_replicated: Option<U256>,
+    nodes: Vec<NodeStreamCount>,
+}
+
+impl Report for NodeStreamCounts {
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![("river block", self.river_block.to_string())];
+        if let Some(total_streams) = self.total_streams {
+            fields.push(("total streams", total_streams.to_string()));
+        }
+        if let Some(incl_replicated) = self.incl_replicated {
+            fields.push(("incl replicated", incl_replicated.to_string()));
+        }
+        fields
+    }
+
+    fn table(&self) -> Option<Table> {
+        Some(Table {
+            columns: ["#streams", "node", "operator", "status", "url"].map(String::from).to_vec(),
+            rows: self.nodes.iter().map(|node| vec![
+                node.stream_count.to_string(),
+                node.address.to_string(),
+                node.operator.to_string(),
+                node.status.to_string(),
+                node.url.clone(),
+            ]).collect(),
+        })
+    }
+}
+
 impl NodeArgs {
-    pub(crate) async fn execute(self, cfg: &config::Config) -> eyre::Result<()> {
+    pub(crate) async fn execute(self, cfg: &config::Config, out: OutputFormat) -> eyre::Result<()> {
         match self.command {
-            NodeCommands::AllNodeStreamCount {} => self.all_node_stream_count(&cfg).await,
-            NodeCommands::NodeStreamCount { .. } => todo!()
+            NodeCommands::AllNodeStreamCount {} => output::render(out, &self.all_node_stream_count(cfg).await?),
+            NodeCommands::NodeStreamCount { node_addr } => output::render(out, &self.node_stream_count(cfg, node_addr).await?),
         }
     }
 
-    pub(crate) async fn all_node_stream_count(&self, cfg: &config::Config) -> eyre::Result<()> {
-        let provider = cfg
-            .river_chain_provider()
-            .wrap_err("Invalid River chain RPC URL") ?;
-        let node_registry = NodeRegistry::new(cfg.registry.address, & provider);
-        let stream_registry = StreamsRegistry::new(cfg.registry.address, & provider);
-        let block = BlockId::Number(BlockNumberOrTag::Number(
-            provider
-                .get_block_number()
-                .await
-                .wrap_err("Failed to get block number")?,
-        ));
-
-        let total_stream_count = stream_registry.getStreamCount()
-            .block(block)
-            .call()
+    async fn node_stream_count(&self, cfg: &config::Config, node_addr: Address) -> eyre::Result<NodeStreamCounts> {
+        let client = cfg.registry_client()?;
+        let block = BlockId::Number(BlockNumberOrTag::Number(cfg.river_block(&client).await?));
+
+        let node = match client.node(node_addr, block).await {
+            Ok(node) => node,
+            Err(TownsError::NotFound) => {
+                return Err(TownsError::InvalidArgumentWithValue(
+                    "node",
+                    format!("{} is not registered", node_addr),
+                ).into());
+            }
+            Err(err) => return Err(err).wrap_err("Failed to get node"),
+        };
+
+        let count = client.stream_count_on_node(node_addr, block)
+            .await.wrap_err("Failed to get node count")?;
+
+        Ok(NodeStreamCounts {
+            river_block: block.as_u64().unwrap(),
+            total_streams: None,
+            incl_replicated: None,
+            nodes: vec![NodeStreamCount {
+                address: node.nodeAddress,
+                operator: node.operator,
+                stream_count: count,
+                status: node.status,
+                url: node.url,
+            }],
+        })
+    }
+
+    async fn all_node_stream_count(&self, cfg: &config::Config) -> eyre::Result<NodeStreamCounts> {
+        let client = cfg.registry_client()?;
+        let block = BlockId::Number(BlockNumberOrTag::Number(cfg.river_block(&client).await?));
+
+        let total_stream_count = client.stream_count(block)
             .await.wrap_err("Failed to get total stream count")?;
 
-        let nodes = node_registry.getAllNodes()
-            .block(block)
-            .call()
+        let nodes = client.nodes(block)
             .await.wrap_err("Failed to get all nodes")?;
 
         let mut result = Vec::new();
 
         for node in nodes {
-            let count = stream_registry.getStreamCountOnNode(node.nodeAddress)
-                .block(block)
-                    .call()
+            let count = client.stream_count_on_node(node.nodeAddress, block)
                 .await.wrap_err("Failed to get node count")?;
 
             result.push(NodeStreamCount{
@@ -184,20 +382,42 @@ impl NodeArgs {
             })
         }
 
-        result.sort_by(|a, b| b.stream_count.cmp(&a.stream_count));
+        result.sort_by_key(|node| std::cmp::Reverse(node.stream_count));
 
-        println!("{:<10}{:<45}{:<45}{:<10}{}", "#streams", "node", "operator", "status", "url");
+        let total: U256 = result.iter().map(|node| node.stream_count).sum();
 
-        let mut total = U256::from(0);
-        for node in result.iter() {
-            total += node.stream_count;
-            println!("{:<10}{:<45}{:<45}{:<10}{}",
-                     node.stream_count, node.address.to_string(), node.operator.to_string(), node.status, node.url);
-        }
+        Ok(NodeStreamCounts {
+            river_block: block.as_u64().unwrap(),
+            total_streams: Some(total_stream_count),
+            incl_replicated: Some(total),
+            nodes: result,
+        })
+    }
+}
 
-        println!("--------------------------------------------------");
-        println!("river block: {} | total streams: {total_stream_count} | incl replicated: {total}", block.as_u64().unwrap());
+#[derive(Debug, Args)]
+#[command(
+    args_conflicts_with_subcommands = true,
+    about = "Manage the local registry event index."
+)]
+pub(crate) struct IndexArgs {
+    #[command(subcommand)]
+    pub command: IndexCommands,
+}
 
-        Ok(())
+impl IndexArgs {
+    pub(crate) async fn execute(self, cfg: &config::Config, out: OutputFormat) -> eyre::Result<()> {
+        match self.command {
+            IndexCommands::Sync { confirmations } => output::render(out, &index::sync(cfg, confirmations).await?),
+        }
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Subcommand)]
+pub(crate) enum IndexCommands {
+    #[command(about = "Store registry logs since the last sync in the local index")]
+    Sync {
+        #[arg(long, help = "number of recent river blocks that are not indexed to avoid reorgs", value_parser=value_parser!(u64), default_value_t = 64)]
+        confirmations: u64,
+    },
+}
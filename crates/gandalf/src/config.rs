@@ -1,32 +1,292 @@
+use crate::index;
+use crate::snapshot::Snapshot;
 use alloy_primitives::{Address, address};
-use alloy_provider::{Provider, ProviderBuilder};
+use alloy_provider::{Provider, ProviderBuilder, WsConnect};
+use alloy_rpc_client::ClientBuilder;
 use alloy_rpc_types::{BlockId, BlockNumberOrTag};
+use chrono::{DateTime, Utc};
 use clap::ValueEnum;
+use eyre::WrapErr;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use towns_protocol_registry::{
+    DEFAULT_LOG_RANGE, RateLimitLayer, RegistryClient, RetryLayer, RetryPolicy,
+};
+use towns_protocol_types::TownsError;
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub(crate) enum Network {
     Alpha,
     Delta,
     Gamma,
     Omega,
+    /// Local development chain, e.g. anvil, with registry contracts deployed.
+    Local,
 }
 
+#[derive(Debug)]
 pub(crate) struct Registry {
     pub address: Address,
     pub deployment_block: BlockId,
 }
 
+#[derive(Debug)]
 pub(crate) struct Config {
     pub river_rpc_url: String,
     pub registry: Registry,
+    /// number of river blocks that are requested in a single get_logs call
+    pub log_range: u64,
+    /// maximum number of RPC requests per second, unlimited if not set
+    pub requests_per_second: Option<NonZeroU32>,
+    /// retries for RPC requests that failed because of transient errors
+    pub retry: RetryPolicy,
+    /// number of get_logs calls that run at the same time during long scans
+    pub concurrency: usize,
+    /// river block all queries of a command are pinned to
+    pub snapshot: Snapshot,
+    /// local registry event index, disabled if not set
+    pub index_path: Option<PathBuf>,
 }
 
 impl Config {
     // river_chain_provider returns an alloy provider for the river chain.
     pub(crate) fn river_chain_provider(&self) -> eyre::Result<impl Provider> {
         let url = self.river_rpc_url.parse()?;
-        Ok(ProviderBuilder::new().connect_http(url))
+        let client = ClientBuilder::default()
+            .layer(RetryLayer::new(self.retry))
+            .layer(RateLimitLayer::new(self.requests_per_second))
+            .http(url);
+        Ok(ProviderBuilder::new().connect_client(client))
     }
+
+    /// river_chain_ws_provider returns an alloy provider for the river chain that is connected
+    /// over WebSocket, with the same retry and rate limit layers as river_chain_provider.
+    pub(crate) async fn river_chain_ws_provider(&self) -> eyre::Result<impl Provider> {
+        let client = ClientBuilder::default()
+            .layer(RetryLayer::new(self.retry))
+            .layer(RateLimitLayer::new(self.requests_per_second))
+            .ws(WsConnect::new(self.river_rpc_url.as_str()))
+            .await?;
+        Ok(ProviderBuilder::new().connect_client(client))
+    }
+
+    /// registry_client returns a client for the stream and node registry on the river chain.
+    pub(crate) fn registry_client(&self) -> eyre::Result<RegistryClient<impl Provider>> {
+        let provider = self
+            .river_chain_provider()
+            .wrap_err("Invalid River chain RPC URL")?;
+        Ok(self.registry_client_with(provider))
+    }
+
+    /// registry_client_with returns a registry client that uses the given provider.
+    pub(crate) fn registry_client_with<P: Provider>(&self, provider: P) -> RegistryClient<P> {
+        let deployment_block = self.registry.deployment_block.as_u64().unwrap_or_default();
+        RegistryClient::new(provider, self.registry.address, deployment_block)
+            .with_log_range(self.log_range)
+            .with_concurrency(self.concurrency)
+    }
+
+    /// Apply the settings from a config file profile.
+    fn with_profile(mut self, profile: &Profile) -> Self {
+        if let Some(rpc_url) = &profile.rpc_url {
+            self.river_rpc_url = rpc_url.clone();
+        }
+        if let Some(registry_address) = profile.registry_address {
+            self.registry.address = registry_address;
+        }
+        if let Some(deployment_block) = profile.deployment_block {
+            self.registry.deployment_block =
+                BlockId::Number(BlockNumberOrTag::Number(deployment_block));
+        }
+        if let Some(log_range) = profile.log_range {
+            self.log_range = log_range;
+        }
+        if let Some(requests_per_second) = profile.requests_per_second {
+            self.requests_per_second = Some(requests_per_second);
+        }
+        if let Some(max_retries) = profile.max_retries {
+            self.retry.max_retries = max_retries;
+        }
+        if let Some(retry_backoff_ms) = profile.retry_backoff_ms {
+            self.retry.initial_backoff = Duration::from_millis(retry_backoff_ms);
+        }
+        if let Some(concurrency) = profile.concurrency {
+            self.concurrency = concurrency.get();
+        }
+        self
+    }
+
+    /// Pin queries to the given block, or to the latest block at the given time.
+    pub(crate) fn with_snapshot(
+        mut self,
+        block: Option<Snapshot>,
+        at: Option<DateTime<Utc>>,
+    ) -> Self {
+        self.snapshot = match (block, at) {
+            (_, Some(at)) => Snapshot::At(at),
+            (Some(block), None) => block,
+            (None, None) => Snapshot::Latest,
+        };
+        self
+    }
+
+    /// River block the queries of the command are pinned to.
+    pub(crate) async fn river_block<P: Provider>(
+        &self,
+        client: &RegistryClient<P>,
+    ) -> eyre::Result<u64> {
+        self.snapshot
+            .resolve(client)
+            .await
+            .wrap_err_with(|| format!("Failed to resolve river block {}", self.snapshot))
+    }
+
+    /// Override the get_logs concurrency when given.
+    pub(crate) fn with_concurrency(mut self, concurrency: Option<NonZeroUsize>) -> Self {
+        if let Some(concurrency) = concurrency {
+            self.concurrency = concurrency.get();
+        }
+        self
+    }
+
+    /// Use the given index, or the default index for the registry if not set.
+    pub(crate) fn with_index(mut self, index_path: Option<PathBuf>, no_index: bool) -> Self {
+        self.index_path = if no_index {
+            None
+        } else {
+            index_path.or_else(|| index::default_index_path(&self.registry.address))
+        };
+        self
+    }
+
+    /// Apply user provided overrides on top of the network defaults.
+    pub(crate) fn with_overrides(
+        mut self,
+        rpc_url: Option<String>,
+        registry_address: Option<Address>,
+        deployment_block: Option<u64>,
+    ) -> eyre::Result<Self> {
+        if let Some(rpc_url) = rpc_url {
+            self.river_rpc_url = rpc_url;
+        }
+        if let Some(registry_address) = registry_address {
+            self.registry.address = registry_address;
+        }
+        if let Some(deployment_block) = deployment_block {
+            self.registry.deployment_block =
+                BlockId::Number(BlockNumberOrTag::Number(deployment_block));
+        }
+
+        if self.registry.address.is_zero() {
+            return Err(TownsError::InvalidArgument("registry address"))
+                .wrap_err("registry address required, set it with --registry-address");
+        }
+
+        Ok(self)
+    }
+}
+
+/// Named network profile in the config file. Unset fields fall back to the defaults of the
+/// profile network.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Profile {
+    pub network: Option<Network>,
+    pub rpc_url: Option<String>,
+    pub registry_address: Option<Address>,
+    pub deployment_block: Option<u64>,
+    pub log_range: Option<u64>,
+    pub requests_per_second: Option<NonZeroU32>,
+    pub max_retries: Option<u32>,
+    pub retry_backoff_ms: Option<u64>,
+    pub concurrency: Option<NonZeroUsize>,
+}
+
+/// Gandalf config file, e.g.
+///
+/// ```toml
+/// [profiles.archive]
+/// network = "omega"
+/// rpc_url = "http://archive.internal:8545"
+/// log_range = 10000
+/// requests_per_second = 25
+/// max_retries = 8
+/// retry_backoff_ms = 500
+/// concurrency = 4
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ConfigFile {
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl ConfigFile {
+    /// Load the config file from the given path or, if not given, from the default location. A
+    /// missing config file at the default location is not an error.
+    pub(crate) fn load(path: Option<&Path>) -> eyre::Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_config_path() {
+                Some(path) => (path, false),
+                None => return Ok(ConfigFile::default()),
+            },
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(content) => ConfigFile::parse(&content)
+                .wrap_err_with(|| format!("Invalid config file {}", path.display())),
+            Err(err) if !required && err.kind() == std::io::ErrorKind::NotFound => {
+                Ok(ConfigFile::default())
+            }
+            Err(err) => {
+                Err(err).wrap_err_with(|| format!("Failed to read config file {}", path.display()))
+            }
+        }
+    }
+
+    pub(crate) fn parse(content: &str) -> eyre::Result<Self> {
+        Ok(toml::from_str(content)?)
+    }
+
+    /// Resolve the config for the given profile, or if no profile is given for the network. A
+    /// profile with the same name as the network overrides the built-in network defaults.
+    pub(crate) fn resolve(&self, network: Network, profile: Option<&str>) -> eyre::Result<Config> {
+        match profile {
+            Some(name) => {
+                let profile = self
+                    .profiles
+                    .get(name)
+                    .ok_or(TownsError::InvalidArgumentWithValue(
+                        "profile",
+                        name.to_string(),
+                    ))
+                    .wrap_err("profile not found in config file")?;
+                Ok(config(profile.network.unwrap_or(network)).with_profile(profile))
+            }
+            None => {
+                let name = network
+                    .to_possible_value()
+                    .map(|value| value.get_name().to_string());
+                match name.and_then(|name| self.profiles.get(&name)) {
+                    Some(profile) => Ok(config(network).with_profile(profile)),
+                    None => Ok(config(network)),
+                }
+            }
+        }
+    }
+}
+
+/// $XDG_CONFIG_HOME/gandalf/config.toml, defaults to ~/.config/gandalf/config.toml
+fn default_config_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("gandalf").join("config.toml"))
 }
 
 pub(crate) fn config(network: Network) -> Config {
@@ -37,6 +297,12 @@ pub(crate) fn config(network: Network) -> Config {
                 address: address!("0x44354786eacbebf981453a05728e1653bc3c5def"),
                 deployment_block: BlockId::Number(BlockNumberOrTag::Number(10499921)),
             },
+            log_range: DEFAULT_LOG_RANGE,
+            requests_per_second: None,
+            retry: RetryPolicy::default(),
+            concurrency: 1,
+            snapshot: Snapshot::Latest,
+            index_path: None,
         },
         Network::Delta => Config {
             river_rpc_url: "https://testnet.rpc.towns.com/http".to_string(),
@@ -44,6 +310,12 @@ pub(crate) fn config(network: Network) -> Config {
                 address: address!("0x9Db19dB285cEd37099D40d27D51B75C4dFa05652"),
                 deployment_block: BlockId::Number(BlockNumberOrTag::Number(15296357)),
             },
+            log_range: DEFAULT_LOG_RANGE,
+            requests_per_second: None,
+            retry: RetryPolicy::default(),
+            concurrency: 1,
+            snapshot: Snapshot::Latest,
+            index_path: None,
         },
         Network::Gamma => Config {
             river_rpc_url: "https://testnet.rpc.towns.com/http".to_string(),
@@ -51,6 +323,12 @@ pub(crate) fn config(network: Network) -> Config {
                 address: address!("0xf18E98D36A6bd1aDb52F776aCc191E69B491c070"),
                 deployment_block: BlockId::Number(BlockNumberOrTag::Number(4577770)),
             },
+            log_range: DEFAULT_LOG_RANGE,
+            requests_per_second: None,
+            retry: RetryPolicy::default(),
+            concurrency: 1,
+            snapshot: Snapshot::Latest,
+            index_path: None,
         },
         Network::Omega => Config {
             river_rpc_url: "https://mainnet.rpc.towns.com/http".to_string(),
@@ -58,6 +336,109 @@ pub(crate) fn config(network: Network) -> Config {
                 address: address!("0x1298c03Fde548dc433a452573E36A713b38A0404"),
                 deployment_block: BlockId::Number(BlockNumberOrTag::Number(134180)),
             },
+            log_range: DEFAULT_LOG_RANGE,
+            requests_per_second: None,
+            retry: RetryPolicy::default(),
+            concurrency: 1,
+            snapshot: Snapshot::Latest,
+            index_path: None,
         },
+        Network::Local => Config {
+            river_rpc_url: "http://127.0.0.1:8545".to_string(),
+            registry: Registry {
+                // registry contracts are deployed by the user, address must be provided
+                address: Address::ZERO,
+                deployment_block: BlockId::Number(BlockNumberOrTag::Number(0)),
+            },
+            log_range: DEFAULT_LOG_RANGE,
+            requests_per_second: None,
+            retry: RetryPolicy::default(),
+            concurrency: 1,
+            snapshot: Snapshot::Latest,
+            index_path: None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG_FILE: &str = r#"
+        [profiles.fork]
+        network = "gamma"
+        rpc_url = "http://fork:8545"
+        deployment_block = 7
+        log_range = 500
+        requests_per_second = 10
+        max_retries = 2
+        retry_backoff_ms = 100
+        concurrency = 4
+
+        [profiles.omega]
+        rpc_url = "http://omega-archive:8545"
+    "#;
+
+    #[test]
+    fn resolve_named_profile() {
+        let file = ConfigFile::parse(CONFIG_FILE).unwrap();
+        let cfg = file.resolve(Network::Omega, Some("fork")).unwrap();
+
+        assert_eq!("http://fork:8545", cfg.river_rpc_url);
+        assert_eq!(
+            config(Network::Gamma).registry.address,
+            cfg.registry.address
+        );
+        assert_eq!(Some(7), cfg.registry.deployment_block.as_u64());
+        assert_eq!(500, cfg.log_range);
+        assert_eq!(NonZeroU32::new(10), cfg.requests_per_second);
+        assert_eq!(2, cfg.retry.max_retries);
+        assert_eq!(Duration::from_millis(100), cfg.retry.initial_backoff);
+        assert_eq!(4, cfg.concurrency);
+        assert_eq!(8, cfg.with_concurrency(NonZeroUsize::new(8)).concurrency);
+
+        assert!(file.resolve(Network::Omega, Some("unknown")).is_err());
+    }
+
+    #[test]
+    fn resolve_network_profile() {
+        let file = ConfigFile::parse(CONFIG_FILE).unwrap();
+
+        let omega = file.resolve(Network::Omega, None).unwrap();
+        assert_eq!("http://omega-archive:8545", omega.river_rpc_url);
+        assert_eq!(DEFAULT_LOG_RANGE, omega.log_range);
+        assert_eq!(RetryPolicy::default(), omega.retry);
+
+        let alpha = file.resolve(Network::Alpha, None).unwrap();
+        assert_eq!(config(Network::Alpha).river_rpc_url, alpha.river_rpc_url);
+    }
+
+    #[test]
+    fn local_network_requires_registry_address() {
+        assert!(
+            config(Network::Local)
+                .with_overrides(None, None, None)
+                .is_err()
+        );
+
+        let registry_address = address!("0x5FbDB2315678afecb367f032d93F642f64180aa3");
+        let cfg = config(Network::Local)
+            .with_overrides(None, Some(registry_address), None)
+            .unwrap();
+        assert_eq!(registry_address, cfg.registry.address);
+        assert_eq!("http://127.0.0.1:8545", cfg.river_rpc_url);
+    }
+
+    #[test]
+    fn override_network_defaults() {
+        let cfg = config(Network::Omega)
+            .with_overrides(Some("http://archive:8545".to_string()), None, Some(42))
+            .unwrap();
+        assert_eq!("http://archive:8545", cfg.river_rpc_url);
+        assert_eq!(Some(42), cfg.registry.deployment_block.as_u64());
+        assert_eq!(
+            config(Network::Omega).registry.address,
+            cfg.registry.address
+        );
     }
 }
@@ -0,0 +1,494 @@
+use crate::index::Selection;
+use crate::output::{self, Report, Table};
+use crate::snapshot::{self, Since};
+use crate::{config, index};
+use alloy_primitives::{Address, FixedBytes};
+use chrono::{DateTime, Utc};
+use eyre::WrapErr;
+use futures::TryStreamExt;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use towns_protocol_registry::{RegistryEvent, RegistryLog};
+use towns_protocol_types::{StreamId, StreamKind};
+
+/// Rejected miniblock registrations for a single stream.
+#[derive(Debug, Serialize)]
+pub(crate) struct StreamFailures {
+    pub stream_id: StreamId,
+    #[serde(serialize_with = "output::display")]
+    pub stream_type: StreamKind,
+    pub failures: usize,
+    /// number of failures per reason
+    pub reasons: BTreeMap<String, usize>,
+    pub first_river_block: u64,
+    pub last_river_block: u64,
+    pub last_miniblock_num: u64,
+    /// true if the stream failed in the ongoing window and no registration succeeded after the
+    /// last failure
+    pub ongoing: bool,
+    /// number of failures per transaction
+    #[serde(skip)]
+    transactions: BTreeMap<FixedBytes<32>, usize>,
+    /// block and log index of the last failure
+    #[serde(skip)]
+    last_failure: (u64, u64),
+    /// true if a miniblock at or after the last failed miniblock was registered after it
+    #[serde(skip)]
+    recovered: bool,
+}
+
+/// Number of failures and affected streams for a reason, stream type or node.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct FailureCount {
+    pub key: String,
+    pub failures: usize,
+    pub streams: usize,
+}
+
+impl FailureCount {
+    fn summary(counts: &[FailureCount]) -> String {
+        counts
+            .iter()
+            .map(|count| {
+                format!(
+                    "{}: {} ({} streams)",
+                    count.key, count.failures, count.streams
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Rejected miniblock registrations in a block range, aggregated by reason, stream type and
+/// stream. Streams are ordered by the number of failures, most first.
+#[derive(Serialize)]
+pub(crate) struct FailureReport {
+    pub from_block: u64,
+    pub from_time: DateTime<Utc>,
+    pub river_block: u64,
+    pub time: DateTime<Utc>,
+    /// failures at or after this block are ongoing
+    pub ongoing_from_block: u64,
+    pub failures: usize,
+    pub ongoing_streams: usize,
+    pub by_reason: Vec<FailureCount>,
+    pub by_stream_type: Vec<FailureCount>,
+    /// only set when the transaction senders are resolved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub by_node: Option<Vec<FailureCount>>,
+    pub streams: Vec<StreamFailures>,
+}
+
+impl Report for FailureReport {
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![
+            (
+                "range",
+                format!(
+                    "{}..={} ({} - {})",
+                    self.from_block,
+                    self.river_block,
+                    output::time(&self.from_time),
+                    output::time(&self.time)
+                ),
+            ),
+            ("failures", self.failures.to_string()),
+            ("streams", self.streams.len().to_string()),
+            ("ongoing streams", self.ongoing_streams.to_string()),
+            ("by reason", FailureCount::summary(&self.by_reason)),
+            (
+                "by stream type",
+                FailureCount::summary(&self.by_stream_type),
+            ),
+        ];
+        if let Some(by_node) = &self.by_node {
+            fields.push(("by node", FailureCount::summary(by_node)));
+        }
+        fields
+    }
+
+    fn table(&self) -> Option<Table> {
+        Some(Table {
+            columns: [
+                "stream_id",
+                "stream_type",
+                "failures",
+                "ongoing",
+                "first_river_block",
+                "last_river_block",
+                "last_miniblock_num",
+                "reasons",
+            ]
+            .map(String::from)
+            .to_vec(),
+            rows: self
+                .streams
+                .iter()
+                .map(|stream| {
+                    vec![
+                        stream.stream_id.to_string(),
+                        stream.stream_type.to_string(),
+                        stream.failures.to_string(),
+                        stream.ongoing.to_string(),
+                        stream.first_river_block.to_string(),
+                        stream.last_river_block.to_string(),
+                        stream.last_miniblock_num.to_string(),
+                        stream
+                            .reasons
+                            .iter()
+                            .map(|(reason, count)| format!("{}: {}", reason, count))
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    ]
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Collects the failed registrations per stream.
+#[derive(Debug, Default)]
+struct FailureStats {
+    streams: BTreeMap<StreamId, StreamFailures>,
+}
+
+impl FailureStats {
+    fn record(&mut self, log: &RegistryLog, stream_types: &[StreamKind]) {
+        let RegistryEvent::MiniblockUpdateFailed {
+            stream_id,
+            last_miniblock_num,
+            reason,
+            ..
+        } = &log.event
+        else {
+            return;
+        };
+
+        let stream_id = StreamId::from_registry(stream_id);
+        if !stream_types.is_empty() && !stream_types.contains(&stream_id.kind()) {
+            return;
+        }
+
+        let stream = self
+            .streams
+            .entry(stream_id)
+            .or_insert_with(|| StreamFailures {
+                stream_id,
+                stream_type: stream_id.kind(),
+                failures: 0,
+                reasons: BTreeMap::new(),
+                first_river_block: log.block_number,
+                last_river_block: log.block_number,
+                last_miniblock_num: *last_miniblock_num,
+                ongoing: false,
+                transactions: BTreeMap::new(),
+                last_failure: (0, 0),
+                recovered: false,
+            });
+        stream.failures += 1;
+        *stream.reasons.entry(reason.clone()).or_default() += 1;
+        stream.last_river_block = log.block_number;
+        stream.last_miniblock_num = *last_miniblock_num;
+        stream.last_failure = (log.block_number, log.log_index);
+        stream.recovered = false;
+        *stream.transactions.entry(log.transaction_hash).or_default() += 1;
+    }
+
+    /// First block of the range in which a successful registration can recover a stream that
+    /// failed at or after `ongoing_from_block`, `None` if no stream failed in that window.
+    fn recovery_from_block(&self, ongoing_from_block: u64) -> Option<u64> {
+        self.streams
+            .values()
+            .map(|stream| stream.last_river_block)
+            .filter(|block| *block >= ongoing_from_block)
+            .min()
+    }
+
+    /// Mark the streams that registered a miniblock at or after the last failed miniblock after
+    /// the last failure.
+    fn record_update(&mut self, log: &RegistryLog) {
+        let RegistryEvent::MiniblockBatch(updates) = &log.event else {
+            return;
+        };
+        for update in updates {
+            let stream_id = StreamId::from_registry(&update.stream_id);
+            let Some(stream) = self.streams.get_mut(&stream_id) else {
+                continue;
+            };
+            if (log.block_number, log.log_index) > stream.last_failure
+                && update.last_miniblock_num >= stream.last_miniblock_num
+            {
+                stream.recovered = true;
+            }
+        }
+    }
+
+    /// Streams ordered by the number of failures, most first. Streams that failed at or after
+    /// `ongoing_from_block` and didn't recover are flagged as ongoing.
+    fn streams(self, ongoing_from_block: u64) -> Vec<StreamFailures> {
+        let mut streams: Vec<StreamFailures> = self
+            .streams
+            .into_values()
+            .map(|mut stream| {
+                stream.ongoing = stream.last_river_block >= ongoing_from_block && !stream.recovered;
+                stream
+            })
+            .collect();
+        streams.sort_by_key(|stream| Reverse(stream.failures));
+        streams
+    }
+}
+
+/// Count failures and distinct streams per key, most failures first.
+fn count_by<'a>(
+    streams: &'a [StreamFailures],
+    keys: impl Fn(&'a StreamFailures) -> Vec<(String, usize)>,
+) -> Vec<FailureCount> {
+    let mut counts: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    for stream in streams {
+        for (key, failures) in keys(stream) {
+            let count = counts.entry(key).or_default();
+            count.0 += failures;
+            count.1 += 1;
+        }
+    }
+
+    let mut counts: Vec<FailureCount> = counts
+        .into_iter()
+        .map(|(key, (failures, streams))| FailureCount {
+            key,
+            failures,
+            streams,
+        })
+        .collect();
+    counts.sort_by_key(|count| Reverse(count.failures));
+    counts
+}
+
+/// Aggregate the rejected miniblock registrations since the given duration or time.
+pub(crate) async fn failures(
+    cfg: &config::Config,
+    since: Since,
+    ongoing: Since,
+    stream_types: &[StreamKind],
+    by_node: bool,
+) -> eyre::Result<FailureReport> {
+    let client = cfg.registry_client()?;
+    let index = index::open(cfg)?;
+    let river_block = cfg.river_block(&client).await?;
+    let from_block = since.first_block(&client, river_block).await?;
+    let ongoing_from_block = ongoing.first_block(&client, river_block).await?;
+
+    let mut stats = FailureStats::default();
+    let range = from_block..=river_block;
+    let mut progress = output::Progress::new("failures", range.clone());
+    let events = index::event_stream(index.as_ref(), &client, Selection::Failures, range)?;
+    let mut events = std::pin::pin!(events);
+    while let Some(chunk) = events.try_next().await.wrap_err("failed to get logs")? {
+        progress.advance(&chunk.range, chunk.events.len());
+        for log in &chunk.events {
+            stats.record(log, stream_types);
+        }
+    }
+
+    // a stream is only ongoing if no registration succeeded after its last failure
+    if let Some(from_block) = stats.recovery_from_block(ongoing_from_block) {
+        let range = from_block..=river_block;
+        let mut progress = output::Progress::new("recoveries", range.clone());
+        let events = index::event_stream(index.as_ref(), &client, Selection::All, range)?;
+        let mut events = std::pin::pin!(events);
+        while let Some(chunk) = events.try_next().await.wrap_err("failed to get logs")? {
+            progress.advance(&chunk.range, chunk.events.len());
+            for log in &chunk.events {
+                stats.record_update(log);
+            }
+        }
+    }
+
+    let streams = stats.streams(ongoing_from_block);
+    let by_reason = count_by(&streams, |stream| {
+        stream
+            .reasons
+            .iter()
+            .map(|(reason, failures)| (reason.clone(), *failures))
+            .collect()
+    });
+    let by_stream_type = count_by(&streams, |stream| {
+        vec![(stream.stream_type.to_string(), stream.failures)]
+    });
+
+    let by_node = if by_node {
+        let transactions = streams
+            .iter()
+            .flat_map(|stream| stream.transactions.keys().copied());
+        let senders = client
+            .transaction_senders(transactions)
+            .await
+            .wrap_err("Failed to get the nodes that submitted the failed registrations")?;
+        Some(count_by(&streams, |stream| {
+            let mut nodes: BTreeMap<Address, usize> = BTreeMap::new();
+            for (transaction, failures) in &stream.transactions {
+                if let Some(node) = senders.get(transaction) {
+                    *nodes.entry(*node).or_default() += failures;
+                }
+            }
+            nodes
+                .into_iter()
+                .map(|(node, failures)| (node.to_string(), failures))
+                .collect()
+        }))
+    } else {
+        None
+    };
+
+    Ok(FailureReport {
+        from_block,
+        from_time: snapshot::block_time(&client, from_block).await?,
+        river_block,
+        time: snapshot::block_time(&client, river_block).await?,
+        ongoing_from_block,
+        failures: streams.iter().map(|stream| stream.failures).sum(),
+        ongoing_streams: streams.iter().filter(|stream| stream.ongoing).count(),
+        by_reason,
+        by_stream_type,
+        by_node,
+        streams,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use towns_protocol_registry::MiniblockUpdate;
+
+    fn failure(block_number: u64, stream: u8, miniblock_num: u64, reason: &str) -> RegistryLog {
+        let mut stream_id = FixedBytes::<32>::ZERO;
+        stream_id[0] = 0x20;
+        stream_id[31] = stream;
+
+        RegistryLog {
+            block_number,
+            block_hash: FixedBytes::ZERO,
+            transaction_hash: FixedBytes::repeat_byte(block_number as u8),
+            log_index: 0,
+            event: RegistryEvent::MiniblockUpdateFailed {
+                stream_id,
+                last_miniblock_hash: FixedBytes::ZERO,
+                last_miniblock_num: miniblock_num,
+                reason: reason.to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn aggregate_failures() {
+        let mut stats = FailureStats::default();
+        for log in [
+            failure(10, 1, 5, "BAD_PREV_MINIBLOCK_HASH"),
+            failure(11, 2, 8, "BAD_PREV_MINIBLOCK_HASH"),
+            failure(12, 1, 5, "MINIBLOCK_NUM_TOO_LOW"),
+            failure(20, 1, 6, "BAD_PREV_MINIBLOCK_HASH"),
+        ] {
+            stats.record(&log, &[]);
+        }
+
+        let mut filtered = FailureStats::default();
+        filtered.record(
+            &failure(10, 1, 5, "BAD_PREV_MINIBLOCK_HASH"),
+            &[StreamKind::Dm],
+        );
+        assert!(filtered.streams.is_empty());
+
+        let streams = stats.streams(15);
+        assert_eq!(2, streams.len());
+        assert_eq!(3, streams[0].failures);
+        assert_eq!(10, streams[0].first_river_block);
+        assert_eq!(20, streams[0].last_river_block);
+        assert_eq!(6, streams[0].last_miniblock_num);
+        assert_eq!(StreamKind::Channel, streams[0].stream_type);
+        assert!(streams[0].ongoing);
+        assert!(!streams[1].ongoing);
+
+        let by_reason = count_by(&streams, |stream| {
+            stream
+                .reasons
+                .iter()
+                .map(|(reason, failures)| (reason.clone(), *failures))
+                .collect()
+        });
+        assert_eq!(
+            vec![
+                FailureCount {
+                    key: "BAD_PREV_MINIBLOCK_HASH".to_string(),
+                    failures: 3,
+                    streams: 2,
+                },
+                FailureCount {
+                    key: "MINIBLOCK_NUM_TOO_LOW".to_string(),
+                    failures: 1,
+                    streams: 1,
+                },
+            ],
+            by_reason
+        );
+    }
+
+    fn update(block_number: u64, stream: u8, miniblock_num: u64) -> RegistryLog {
+        let RegistryEvent::MiniblockUpdateFailed { stream_id, .. } =
+            failure(block_number, stream, miniblock_num, "").event
+        else {
+            unreachable!()
+        };
+        RegistryLog {
+            event: RegistryEvent::MiniblockBatch(vec![MiniblockUpdate {
+                stream_id,
+                last_miniblock_hash: FixedBytes::ZERO,
+                last_miniblock_num: miniblock_num,
+                is_sealed: false,
+            }]),
+            ..failure(block_number, stream, miniblock_num, "")
+        }
+    }
+
+    #[test]
+    fn ongoing_until_recovered() {
+        let mut stats = FailureStats::default();
+        stats.record(&failure(10, 3, 2, "BAD_PREV_MINIBLOCK_HASH"), &[]);
+        stats.record(&failure(20, 1, 6, "BAD_PREV_MINIBLOCK_HASH"), &[]);
+        stats.record(&failure(21, 2, 8, "BAD_PREV_MINIBLOCK_HASH"), &[]);
+        stats.record(&failure(21, 2, 8, "BAD_PREV_MINIBLOCK_HASH"), &[]);
+        assert_eq!(Some(20), stats.recovery_from_block(15));
+        assert_eq!(None, stats.recovery_from_block(22));
+
+        for log in [
+            // before the last failure, or an older miniblock
+            update(19, 2, 9),
+            update(22, 2, 7),
+            // registered the failed miniblock after the failure
+            update(25, 1, 6),
+        ] {
+            stats.record_update(&log);
+        }
+
+        let streams = stats.streams(15);
+        let ongoing = |last: u8| {
+            streams
+                .iter()
+                .find(|stream| stream.stream_id.as_fixed_bytes32()[31] == last)
+                .unwrap()
+                .ongoing
+        };
+        assert!(!ongoing(1));
+        assert!(ongoing(2));
+        assert!(!ongoing(3));
+
+        // failures in the same transaction are counted per failure
+        let stream = streams.iter().find(|stream| stream.failures == 2).unwrap();
+        assert_eq!(
+            Some(&2),
+            stream.transactions.get(&FixedBytes::repeat_byte(21))
+        );
+    }
+}
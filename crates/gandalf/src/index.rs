@@ -0,0 +1,691 @@
+use crate::config;
+use crate::output::{Progress, Report};
+use alloy_primitives::{Address, Bytes, FixedBytes, LogData};
+use alloy_provider::Provider;
+use alloy_rpc_types::Log;
+use eyre::{WrapErr, eyre};
+use futures::{Stream, StreamExt};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::Serialize;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use towns_protocol_registry::{
+    self as registry, MINIBLOCK_UPDATE_FAILED_SIGNATURE, RegistryClient, RegistryEvent, RegistryLog,
+};
+use towns_protocol_types::{StreamId, TownsError};
+
+/// Number of log ranges that are stored in a single transaction while syncing.
+const SYNC_BATCH_LOG_RANGES: u64 = 10;
+
+/// Number of blocks that are read from the index in a single query while streaming events.
+const INDEX_READ_BLOCKS: u64 = 100_000;
+
+/// Per stream reads of more blocks than this that are not in the index print a hint to sync it.
+const UNINDEXED_STREAM_BLOCKS: u64 = 10_000;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS meta (
+    registry BLOB NOT NULL,
+    deployment_block INTEGER NOT NULL,
+    synced_to INTEGER
+);
+CREATE TABLE IF NOT EXISTS logs (
+    block_number INTEGER NOT NULL,
+    log_index INTEGER NOT NULL,
+    block_hash BLOB NOT NULL,
+    transaction_hash BLOB NOT NULL,
+    topics BLOB NOT NULL,
+    data BLOB NOT NULL,
+    PRIMARY KEY (block_number, log_index)
+);
+CREATE TABLE IF NOT EXISTS stream_logs (
+    stream_id BLOB NOT NULL,
+    block_number INTEGER NOT NULL,
+    log_index INTEGER NOT NULL,
+    PRIMARY KEY (stream_id, block_number, log_index)
+);
+";
+
+/// Local copy of all registry logs from the registry deployment block up to the synced block.
+#[derive(Debug)]
+pub(crate) struct Index {
+    conn: Connection,
+    registry: Address,
+    deployment_block: u64,
+}
+
+impl Index {
+    /// Open the index at the given path, it is created if it doesn't exist.
+    pub(crate) fn open(
+        path: &Path,
+        registry: Address,
+        deployment_block: u64,
+    ) -> eyre::Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .wrap_err_with(|| format!("Failed to create index directory {}", dir.display()))?;
+        }
+        let conn = Connection::open(path)
+            .wrap_err_with(|| format!("Failed to open index {}", path.display()))?;
+        Self::init(conn, registry, deployment_block)
+    }
+
+    /// Open the index at the given path, returns `None` if there is no index or if it was
+    /// created for another registry or deployment block, e.g. because of a `--deployment-block`
+    /// override.
+    pub(crate) fn open_existing(
+        path: &Path,
+        registry: Address,
+        deployment_block: u64,
+    ) -> eyre::Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let conn = Connection::open(path)
+            .wrap_err_with(|| format!("Failed to open index {}", path.display()))?;
+        match Self::meta(&conn)? {
+            Some((address, block)) if (address, block) != (registry, deployment_block) => {
+                eprintln!(
+                    "ignoring index {} of registry {} deployed in block {}, registry events are read from the chain",
+                    path.display(),
+                    address,
+                    block
+                );
+                Ok(None)
+            }
+            _ => Self::init(conn, registry, deployment_block).map(Some),
+        }
+    }
+
+    /// Registry address and deployment block the index was created for, `None` for a new index.
+    fn meta(conn: &Connection) -> eyre::Result<Option<(Address, u64)>> {
+        conn.execute_batch(SCHEMA)?;
+
+        let meta: Option<(Vec<u8>, u64)> = conn
+            .query_row("SELECT registry, deployment_block FROM meta", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .optional()?;
+        Ok(meta.map(|(address, block)| (Address::from_slice(&address), block)))
+    }
+
+    fn init(conn: Connection, registry: Address, deployment_block: u64) -> eyre::Result<Self> {
+        match Self::meta(&conn)? {
+            None => {
+                conn.execute(
+                    "INSERT INTO meta (registry, deployment_block) VALUES (?1, ?2)",
+                    params![registry.as_slice(), deployment_block],
+                )?;
+            }
+            Some((address, block)) if address == registry && block == deployment_block => {}
+            Some((address, block)) => {
+                return Err(eyre!(
+                    "index belongs to registry {} deployed in block {}",
+                    address,
+                    block
+                ));
+            }
+        }
+
+        Ok(Index {
+            conn,
+            registry,
+            deployment_block,
+        })
+    }
+
+    /// Last river block that is stored in the index.
+    pub(crate) fn synced_to(&self) -> eyre::Result<Option<u64>> {
+        Ok(self
+            .conn
+            .query_row("SELECT synced_to FROM meta", [], |row| row.get(0))?)
+    }
+
+    /// Part of the given range that the index covers.
+    pub(crate) fn covered(
+        &self,
+        range: &RangeInclusive<u64>,
+    ) -> eyre::Result<Option<RangeInclusive<u64>>> {
+        let Some(synced_to) = self.synced_to()? else {
+            return Ok(None);
+        };
+        let from = (*range.start()).max(self.deployment_block);
+        let to = (*range.end()).min(synced_to);
+        Ok((from <= to).then_some(from..=to))
+    }
+
+    /// Store the logs for the given range, which must directly follow the synced range.
+    fn append(&mut self, range: RangeInclusive<u64>, logs: &[Log]) -> eyre::Result<()> {
+        let expected = self
+            .synced_to()?
+            .map_or(self.deployment_block, |synced_to| synced_to + 1);
+        if *range.start() != expected {
+            return Err(eyre!(
+                "range {:?} doesn't follow synced block {}",
+                range,
+                expected
+            ));
+        }
+
+        let tx = self.conn.transaction()?;
+        {
+            let mut insert_log = tx.prepare(
+                "INSERT OR REPLACE INTO logs (block_number, log_index, block_hash, transaction_hash, topics, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            let mut insert_stream = tx.prepare(
+                "INSERT OR IGNORE INTO stream_logs (stream_id, block_number, log_index) VALUES (?1, ?2, ?3)",
+            )?;
+
+            for log in logs {
+                if log.address() != self.registry {
+                    continue;
+                }
+
+                let block_number = log.block_number.unwrap_or_default();
+                let log_index = log.log_index.unwrap_or_default();
+                let topics: Vec<u8> = log.topics().iter().flat_map(|topic| topic.0).collect();
+
+                insert_log.execute(params![
+                    block_number,
+                    log_index,
+                    log.block_hash.unwrap_or_default().as_slice(),
+                    log.transaction_hash.unwrap_or_default().as_slice(),
+                    topics,
+                    log.data().data.as_ref(),
+                ])?;
+
+                // the raw log is kept even if it can't be decoded, a failed decode must not stop
+                // the sync
+                if let Some(decoded) = registry::decode_log(log) {
+                    if let RegistryEvent::Undecodable { reason, .. } = &decoded.event {
+                        eprintln!(
+                            "registry log {} in block {} couldn't be decoded and isn't indexed per stream: {}",
+                            log_index, block_number, reason
+                        );
+                    }
+                    for stream_id in decoded.event.stream_ids() {
+                        insert_stream.execute(params![
+                            stream_id.as_slice(),
+                            block_number,
+                            log_index
+                        ])?;
+                    }
+                }
+            }
+        }
+        tx.execute("UPDATE meta SET synced_to = ?1", params![range.end()])?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// All registry events in the given range.
+    pub(crate) fn events(&self, range: RangeInclusive<u64>) -> eyre::Result<Vec<RegistryLog>> {
+        self.query(
+            "SELECT block_number, log_index, block_hash, transaction_hash, topics, data FROM logs
+             WHERE block_number BETWEEN ?1 AND ?2 ORDER BY block_number, log_index",
+            params![range.start(), range.end()],
+        )
+    }
+
+    /// All events in the given range emitted by logs with the given event signature.
+    pub(crate) fn events_with_signature(
+        &self,
+        signature: FixedBytes<32>,
+        range: RangeInclusive<u64>,
+    ) -> eyre::Result<Vec<RegistryLog>> {
+        self.query(
+            "SELECT block_number, log_index, block_hash, transaction_hash, topics, data FROM logs
+             WHERE block_number BETWEEN ?1 AND ?2 AND substr(topics, 1, 32) = ?3
+             ORDER BY block_number, log_index",
+            params![range.start(), range.end(), signature.as_slice()],
+        )
+    }
+
+    /// All events for the stream in the given range, miniblock batches only contain the
+    /// updates for the stream.
+    pub(crate) fn stream_events(
+        &self,
+        stream_id: &StreamId,
+        range: RangeInclusive<u64>,
+    ) -> eyre::Result<Vec<RegistryLog>> {
+        let id = stream_id.as_fixed_bytes32();
+        Ok(self
+            .query(
+                "SELECT l.block_number, l.log_index, l.block_hash, l.transaction_hash, l.topics, l.data
+                 FROM stream_logs s JOIN logs l USING (block_number, log_index)
+                 WHERE s.stream_id = ?1 AND s.block_number BETWEEN ?2 AND ?3
+                 ORDER BY l.block_number, l.log_index",
+                params![id.as_slice(), range.start(), range.end()],
+            )?
+            .iter()
+            .filter_map(|log| log.for_stream(&id))
+            .collect())
+    }
+
+    fn query(&self, sql: &str, params: impl rusqlite::Params) -> eyre::Result<Vec<RegistryLog>> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt.query_map(params, |row| {
+            Ok((
+                row.get::<_, u64>(0)?,
+                row.get::<_, u64>(1)?,
+                row.get::<_, Vec<u8>>(2)?,
+                row.get::<_, Vec<u8>>(3)?,
+                row.get::<_, Vec<u8>>(4)?,
+                row.get::<_, Vec<u8>>(5)?,
+            ))
+        })?;
+
+        let mut events = Vec::new();
+        for row in rows {
+            let (block_number, log_index, block_hash, transaction_hash, topics, data) = row?;
+            let log = Log {
+                inner: alloy_primitives::Log {
+                    address: self.registry,
+                    data: LogData::new_unchecked(
+                        topics.chunks(32).map(FixedBytes::from_slice).collect(),
+                        Bytes::from(data),
+                    ),
+                },
+                block_number: Some(block_number),
+                block_hash: Some(FixedBytes::from_slice(&block_hash)),
+                transaction_hash: Some(FixedBytes::from_slice(&transaction_hash)),
+                log_index: Some(log_index),
+                ..Default::default()
+            };
+            events.extend(registry::decode_log(&log));
+        }
+
+        Ok(events)
+    }
+}
+
+/// Open the configured index if it exists.
+pub(crate) fn open(cfg: &config::Config) -> eyre::Result<Option<Index>> {
+    let Some(path) = &cfg.index_path else {
+        return Ok(None);
+    };
+    let deployment_block = cfg.registry.deployment_block.as_u64().unwrap_or_default();
+    Index::open_existing(path, cfg.registry.address, deployment_block)
+}
+
+/// Registry events that are read by [`event_stream`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Selection<'a> {
+    All,
+    /// events for the stream, miniblock batches only contain the updates for the stream
+    Stream(&'a StreamId),
+    /// rejected miniblock registrations
+    Failures,
+}
+
+/// Registry events in a block range.
+#[derive(Debug)]
+pub(crate) struct EventChunk {
+    pub range: RangeInclusive<u64>,
+    pub events: Vec<RegistryLog>,
+    /// number of logs that were downloaded from the chain to find the events, `None` for blocks
+    /// read from the index, it only reads the logs for the selection
+    pub scanned: Option<usize>,
+}
+
+/// Stream the selected registry events in the range as chunks in block order. Blocks covered
+/// by the index are read from it, the remainder is read from the chain. The index only reads
+/// the logs for the selection. The RPC node can only filter logs by event signature, no
+/// registry event has the stream id as indexed topic. Selecting a stream in blocks that are not
+/// indexed downloads nearly all registry logs, a hint to sync the index is printed for long
+/// ranges.
+pub(crate) fn event_stream<'a, P: Provider>(
+    index: Option<&'a Index>,
+    client: &'a RegistryClient<P>,
+    selection: Selection<'a>,
+    range: RangeInclusive<u64>,
+) -> eyre::Result<impl Stream<Item = eyre::Result<EventChunk>> + 'a> {
+    let covered = match index {
+        Some(index) => index.covered(&range)?.map(|covered| (index, covered)),
+        None => None,
+    };
+    let from = match &covered {
+        Some((_, covered)) => covered.end().checked_add(1),
+        None => Some(*range.start()),
+    };
+    let remainder = from
+        .filter(|from| from <= range.end())
+        .map(|from| from..=*range.end());
+
+    let indexed = covered
+        .into_iter()
+        .flat_map(|(index, covered)| {
+            split_range(covered, INDEX_READ_BLOCKS).map(move |range| (index, range))
+        })
+        .map(move |(index, range)| {
+            let events = match selection {
+                Selection::All => index.events(range.clone())?,
+                Selection::Stream(stream_id) => index.stream_events(stream_id, range.clone())?,
+                Selection::Failures => {
+                    index.events_with_signature(MINIBLOCK_UPDATE_FAILED_SIGNATURE, range.clone())?
+                }
+            };
+            Ok(EventChunk {
+                range,
+                events: events.into_iter().filter_map(decodable).collect(),
+                scanned: None,
+            })
+        });
+
+    if let (Selection::Stream(_), Some(remainder)) = (selection, &remainder) {
+        if remainder.end() - remainder.start() + 1 > UNINDEXED_STREAM_BLOCKS {
+            eprintln!(
+                "blocks {}..={} are not in the local index, all stream logs are downloaded and \
+                 filtered locally, run `gandalf index sync` to speed this up",
+                remainder.start(),
+                remainder.end()
+            );
+        }
+    }
+
+    let remote = futures::stream::iter(remainder.map(move |range| match selection {
+        Selection::All => client.log_chunks(range).left_stream().left_stream(),
+        Selection::Stream(_) => client.stream_log_chunks(range).right_stream().left_stream(),
+        Selection::Failures => client.failure_log_chunks(range).right_stream(),
+    }))
+    .flatten()
+    .map(move |chunk| {
+        let chunk = chunk?;
+        let mut events = Vec::new();
+        for log in &chunk.logs {
+            let Some(log) = registry::decode_log(log).and_then(decodable) else {
+                continue;
+            };
+            match selection {
+                Selection::Stream(stream_id) => {
+                    events.extend(log.for_stream(&stream_id.as_fixed_bytes32()))
+                }
+                Selection::All | Selection::Failures => events.push(log),
+            }
+        }
+        Ok(EventChunk {
+            range: chunk.range,
+            events,
+            scanned: Some(chunk.logs.len()),
+        })
+    });
+
+    Ok(futures::stream::iter(indexed).chain(remote))
+}
+
+/// Returns `None` with a warning for logs that couldn't be decoded, so a single malformed log
+/// doesn't end a scan.
+fn decodable(log: RegistryLog) -> Option<RegistryLog> {
+    if let RegistryEvent::Undecodable { reason, .. } = &log.event {
+        eprintln!(
+            "skipping undecodable registry log {} in block {}, tx {}: {}",
+            log.log_index, log.block_number, log.transaction_hash, reason
+        );
+        return None;
+    }
+    Some(log)
+}
+
+/// Split the range in consecutive ranges of at most `blocks` blocks.
+fn split_range(
+    range: RangeInclusive<u64>,
+    blocks: u64,
+) -> impl Iterator<Item = RangeInclusive<u64>> {
+    let end = *range.end();
+    range
+        .step_by(blocks as usize)
+        .map(move |start| start..=end.min(start.saturating_add(blocks - 1)))
+}
+
+#[derive(Serialize)]
+pub(crate) struct SyncReport {
+    pub path: PathBuf,
+    pub from_block: u64,
+    pub synced_to: Option<u64>,
+    pub logs: usize,
+}
+
+impl Report for SyncReport {
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("index", self.path.display().to_string()),
+            ("from block", self.from_block.to_string()),
+            (
+                "synced to",
+                self.synced_to
+                    .map(|block| block.to_string())
+                    .unwrap_or_default(),
+            ),
+            ("new logs", self.logs.to_string()),
+        ]
+    }
+}
+
+/// Store all registry logs since the last sync up to `confirmations` blocks behind the chain head.
+pub(crate) async fn sync(cfg: &config::Config, confirmations: u64) -> eyre::Result<SyncReport> {
+    let path = cfg
+        .index_path
+        .clone()
+        .ok_or(TownsError::InvalidArgument("index path"))
+        .wrap_err("no index path, set it with --index")?;
+    let client = cfg.registry_client()?;
+    let mut index = Index::open(&path, client.address(), client.deployment_block())?;
+
+    let from_block = index
+        .synced_to()?
+        .map_or(index.deployment_block, |synced_to| synced_to + 1);
+    let head = client.block_number().await?.saturating_sub(confirmations);
+    let batch = cfg.log_range.max(1) * SYNC_BATCH_LOG_RANGES;
+
+    let mut logs = 0;
+    let mut progress = Progress::new("sync", from_block..=head);
+    let mut from = from_block;
+    while from <= head {
+        let to = head.min(from + batch - 1);
+        let batch_logs = client
+            .logs(from..=to)
+            .await
+            .wrap_err("Failed to get registry logs")?;
+        index.append(from..=to, &batch_logs)?;
+        logs += batch_logs.len();
+
+        progress.advance(&(from..=to), batch_logs.len());
+        from = to + 1;
+    }
+
+    Ok(SyncReport {
+        path,
+        from_block,
+        synced_to: index.synced_to()?,
+        logs,
+    })
+}
+
+/// Default index location for the registry, $XDG_DATA_HOME/gandalf/<registry>.sqlite or
+/// ~/.local/share/gandalf/<registry>.sqlite.
+pub(crate) fn default_index_path(registry: &Address) -> Option<PathBuf> {
+    let data_dir = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share"))
+        })?;
+    Some(
+        data_dir
+            .join("gandalf")
+            .join(format!("{}.sqlite", registry)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_sol_types::{SolEvent, SolType};
+    use towns_protocol_contracts::{
+        SetMiniblock, SetMiniblockArray, StreamEventType, StreamsRegistry,
+    };
+
+    const REGISTRY: Address = Address::repeat_byte(0x11);
+
+    fn stream_id(last: u8) -> StreamId {
+        let mut id = FixedBytes::<32>::ZERO;
+        id[0] = towns_protocol_types::CHANNEL_STREAM_ID_PREFIX;
+        id[31] = last;
+        StreamId::try_from(&id).unwrap()
+    }
+
+    fn miniblock_log(block_number: u64, log_index: u64, streams: &[u8]) -> Log {
+        let data = SetMiniblockArray::abi_encode_params(
+            &streams
+                .iter()
+                .map(|stream| SetMiniblock {
+                    streamId: stream_id(*stream).as_fixed_bytes32(),
+                    prevMiniBlockHash: FixedBytes::ZERO,
+                    lastMiniblockHash: FixedBytes::repeat_byte(*stream),
+                    lastMiniblockNum: block_number,
+                    isSealed: false,
+                })
+                .collect::<Vec<_>>(),
+        );
+        let event = StreamsRegistry::StreamUpdated {
+            eventType: StreamEventType::LastMiniblockBatchUpdated as u8,
+            data: data.into(),
+        };
+        Log {
+            inner: alloy_primitives::Log {
+                address: REGISTRY,
+                data: event.encode_log_data(),
+            },
+            block_number: Some(block_number),
+            block_hash: Some(FixedBytes::repeat_byte(0xbb)),
+            transaction_hash: Some(FixedBytes::repeat_byte(0xcc)),
+            log_index: Some(log_index),
+            ..Default::default()
+        }
+    }
+
+    fn index() -> Index {
+        Index::init(Connection::open_in_memory().unwrap(), REGISTRY, 100).unwrap()
+    }
+
+    #[test]
+    fn append_and_query() {
+        let mut index = index();
+        assert_eq!(None, index.covered(&(0..=200)).unwrap());
+
+        index
+            .append(
+                100..=150,
+                &[miniblock_log(110, 0, &[1, 2]), miniblock_log(120, 3, &[2])],
+            )
+            .unwrap();
+        index
+            .append(151..=160, &[miniblock_log(155, 1, &[1])])
+            .unwrap();
+
+        assert_eq!(Some(160), index.synced_to().unwrap());
+        assert_eq!(Some(100..=160), index.covered(&(0..=200)).unwrap());
+        assert_eq!(Some(150..=155), index.covered(&(150..=155)).unwrap());
+        assert_eq!(None, index.covered(&(161..=200)).unwrap());
+
+        let events = index.events(100..=160).unwrap();
+        assert_eq!(
+            vec![(110, 0), (120, 3), (155, 1)],
+            events
+                .iter()
+                .map(|log| (log.block_number, log.log_index))
+                .collect::<Vec<_>>()
+        );
+
+        let stream_events = index.stream_events(&stream_id(1), 100..=160).unwrap();
+        assert_eq!(
+            vec![110, 155],
+            stream_events
+                .iter()
+                .map(|log| log.block_number)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            registry::decode_log(&miniblock_log(110, 0, &[1])).map(|log| log.event),
+            Some(stream_events[0].event.clone())
+        );
+    }
+
+    #[test]
+    fn append_undecodable_log() {
+        let mut index = index();
+        let mut log = miniblock_log(110, 0, &[1]);
+        log.inner.data = LogData::new_unchecked(
+            log.topics().to_vec(),
+            StreamsRegistry::StreamUpdated {
+                eventType: StreamEventType::LastMiniblockBatchUpdated as u8,
+                data: vec![0x01].into(),
+            }
+            .encode_data()
+            .into(),
+        );
+
+        index
+            .append(100..=150, &[log, miniblock_log(120, 0, &[1])])
+            .unwrap();
+
+        assert_eq!(Some(150), index.synced_to().unwrap());
+        let events = index.events(100..=150).unwrap();
+        assert_eq!("Undecodable", events[0].event.name());
+        assert_eq!(
+            vec![120],
+            index
+                .stream_events(&stream_id(1), 100..=150)
+                .unwrap()
+                .iter()
+                .map(|log| log.block_number)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn append_requires_contiguous_ranges() {
+        let mut index = index();
+        assert!(index.append(101..=150, &[]).is_err());
+        index.append(100..=150, &[]).unwrap();
+        assert!(index.append(152..=160, &[]).is_err());
+    }
+
+    #[test]
+    fn index_belongs_to_registry() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        conn.execute(
+            "INSERT INTO meta (registry, deployment_block) VALUES (?1, ?2)",
+            params![Address::repeat_byte(0x22).as_slice(), 100u64],
+        )
+        .unwrap();
+        assert!(Index::init(conn, REGISTRY, 100).is_err());
+    }
+
+    #[test]
+    fn ignore_index_of_other_deployment() {
+        let path =
+            std::env::temp_dir().join(format!("gandalf-index-{}.sqlite", std::process::id()));
+        Index::open(&path, REGISTRY, 100).unwrap();
+
+        assert!(
+            Index::open_existing(&path, REGISTRY, 100)
+                .unwrap()
+                .is_some()
+        );
+        assert!(Index::open_existing(&path, REGISTRY, 90).unwrap().is_none());
+        assert!(Index::open(&path, REGISTRY, 90).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn split_ranges() {
+        assert_eq!(
+            vec![0..=9, 10..=19, 20..=24],
+            split_range(0..=24, 10).collect::<Vec<_>>()
+        );
+        assert_eq!(vec![5..=5], split_range(5..=5, 10).collect::<Vec<_>>());
+    }
+}
@@ -1,18 +1,94 @@
 mod args;
 mod config;
+mod failures;
+mod index;
+mod miniblock;
+mod output;
+mod snapshot;
 mod stream;
+mod timeline;
+mod watch;
 
 use clap::Parser;
 use eyre::Result;
+use std::process::ExitCode;
+use towns_protocol_types::{ErrorKind, TownsError};
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            exit_code(&err)
+        }
+    }
+}
+
+async fn run() -> Result<()> {
     let opts = args::Opts::parse();
-    let cfg = config::config(opts.network);
+    let cfg = config::ConfigFile::load(opts.config.as_deref())?
+        .resolve(opts.network, opts.profile.as_deref())?
+        .with_overrides(
+        opts.rpc_url,
+        opts.registry_address,
+        opts.deployment_block,
+    )?
+        .with_index(opts.index, opts.no_index)
+        .with_concurrency(opts.concurrency)
+        .with_snapshot(opts.block, opts.at);
 
     match opts.command {
-        args::Commands::Stream(args) => args.execute(&cfg).await,
-        args::Commands::Miniblock(args) => args.execute(&cfg).await,
-        args::Commands::Node(args) => args.execute(&cfg).await,
+        args::Commands::Stream(args) => args.execute(&cfg, opts.output).await,
+        args::Commands::Miniblock(args) => args.execute(&cfg, opts.output).await,
+        args::Commands::Node(args) => args.execute(&cfg, opts.output).await,
+        args::Commands::Index(args) => args.execute(&cfg, opts.output).await,
+    }
+}
+
+/// Exit code for the error, based on the first [`TownsError`] in the error chain:
+///
+/// - 1: other errors
+/// - 2: invalid input
+/// - 3: not found
+/// - 4: contract call reverted
+/// - 5: invalid or inconsistent data
+/// - 6: rpc node returned an error
+/// - 7: rpc node unreachable or rate limited, retrying later can succeed
+fn exit_code(err: &eyre::Report) -> ExitCode {
+    let Some(err) = err.chain().find_map(|err| err.downcast_ref::<TownsError>()) else {
+        return ExitCode::FAILURE;
+    };
+
+    let code = match err.kind() {
+        ErrorKind::InvalidInput => 2,
+        ErrorKind::NotFound => 3,
+        ErrorKind::Reverted => 4,
+        ErrorKind::Decode | ErrorKind::Integrity => 5,
+        ErrorKind::Rpc if err.is_retryable() => 7,
+        ErrorKind::Rpc => 6,
+        ErrorKind::Transport | ErrorKind::RateLimited => 7,
+    };
+    ExitCode::from(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::WrapErr;
+
+    #[test]
+    fn exit_code_from_error_chain() {
+        let err = Err::<(), _>(TownsError::NotFound)
+            .wrap_err("Failed to get node")
+            .unwrap_err();
+        assert_eq!(ExitCode::from(3), exit_code(&err));
+
+        let err = Err::<(), _>(TownsError::InvalidArgument("registry address"))
+            .wrap_err("registry address required")
+            .unwrap_err();
+        assert_eq!(ExitCode::from(2), exit_code(&err));
+
+        assert_eq!(ExitCode::FAILURE, exit_code(&eyre::eyre!("other")));
     }
 }
@@ -0,0 +1,816 @@
+use crate::{config, index};
+use crate::output::{self, Report, Table};
+use alloy_primitives::{Address, Bytes, FixedBytes};
+use alloy_rpc_types::{BlockId, BlockNumberOrTag};
+use eyre::WrapErr;
+use prost::Message;
+use serde::Serialize;
+use towns_protocol_types::protocol::{
+    GetLastMiniblockHashRequest, GetLastMiniblockHashResponse, GetMiniblocksRequest,
+    GetMiniblocksResponse, Miniblock, MiniblockHeader,
+};
+use towns_protocol_registry::RegistryEvent;
+use towns_protocol_types::{StreamId, TownsError};
+
+/// Number of miniblocks that are requested from a node in a single call.
+const MINIBLOCK_BATCH_SIZE: u64 = 100;
+
+/// NodeClient calls the stream service on a stream node over the connect protocol.
+pub(crate) struct NodeClient {
+    url: String,
+    http: reqwest::Client,
+}
+
+impl NodeClient {
+    pub(crate) fn new(url: &str) -> Self {
+        NodeClient {
+            url: url.trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Get the miniblocks in the range [from, to_exclusive). Nodes can return less miniblocks
+    /// than requested, in that case the remaining miniblocks are requested until the node has no
+    /// more miniblocks to return.
+    pub(crate) async fn get_miniblocks(
+        &self,
+        stream_id: &StreamId,
+        from: u64,
+        to_exclusive: u64,
+    ) -> eyre::Result<Vec<Miniblock>> {
+        let mut miniblocks = Vec::new();
+        let mut next = from;
+
+        while next < to_exclusive {
+            let request = GetMiniblocksRequest {
+                stream_id: stream_id.as_bytes().to_vec(),
+                from_inclusive: next as i64,
+                to_exclusive: to_exclusive as i64,
+                omit_snapshots: true,
+            };
+
+            let response: GetMiniblocksResponse = self
+                .call("river.StreamService/GetMiniblocks", &request)
+                .await?;
+
+            if response.miniblocks.is_empty() {
+                break;
+            }
+
+            next += response.miniblocks.len() as u64;
+            miniblocks.extend(response.miniblocks);
+        }
+
+        Ok(miniblocks)
+    }
+
+    /// Get the hash and number of the last miniblock the node has for the stream.
+    pub(crate) async fn get_last_miniblock_hash(
+        &self,
+        stream_id: &StreamId,
+    ) -> eyre::Result<(u64, FixedBytes<32>)> {
+        let request = GetLastMiniblockHashRequest {
+            stream_id: stream_id.as_bytes().to_vec(),
+        };
+
+        let response: GetLastMiniblockHashResponse = self
+            .call("river.StreamService/GetLastMiniblockHash", &request)
+            .await?;
+
+        Ok((
+            response.miniblock_num as u64,
+            to_hash(&response.hash).ok_or(TownsError::InvalidArgument("miniblock hash"))?,
+        ))
+    }
+
+    async fn call<Req: Message, Resp: Message + Default>(
+        &self,
+        method: &str,
+        request: &Req,
+    ) -> eyre::Result<Resp> {
+        let response = self
+            .http
+            .post(format!("{}/{}", self.url, method))
+            .header("Content-Type", "application/proto")
+            .header("Connect-Protocol-Version", "1")
+            .body(request.encode_to_vec())
+            .send()
+            .await
+            .wrap_err_with(|| format!("Failed to call {} on {}", method, self.url))?;
+
+        let status = response.status();
+        let body = response.bytes().await?;
+
+        if !status.is_success() {
+            return Err(eyre::eyre!(
+                "{} on {} failed with status {}: {}",
+                method,
+                self.url,
+                status,
+                String::from_utf8_lossy(&body)
+            ));
+        }
+
+        Resp::decode(body).wrap_err_with(|| format!("Failed to decode {} response", method))
+    }
+}
+
+/// Get the miniblock with the given number, it must have the given hash.
+pub(crate) async fn fetch_miniblock(
+    client: &NodeClient,
+    stream_id: &StreamId,
+    miniblock_num: u64,
+    miniblock_hash: &FixedBytes<32>,
+) -> eyre::Result<(MiniblockHeader, Miniblock)> {
+    let to_exclusive = miniblock_num.checked_add(1).ok_or(TownsError::NotFound)?;
+    let miniblock = client
+        .get_miniblocks(stream_id, miniblock_num, to_exclusive)
+        .await?
+        .into_iter()
+        .next()
+        .filter(|miniblock| has_hash(miniblock, miniblock_hash))
+        .ok_or(TownsError::NotFound)?;
+
+    let header = miniblock.decode_header()?;
+    Ok((header, miniblock))
+}
+
+/// Search backwards from tip for the miniblock with the given hash. This takes a request per
+/// MINIBLOCK_BATCH_SIZE miniblocks, use fetch_miniblock if the miniblock number is known.
+pub(crate) async fn find_miniblock(
+    client: &NodeClient,
+    stream_id: &StreamId,
+    tip: u64,
+    miniblock_hash: &FixedBytes<32>,
+) -> eyre::Result<(MiniblockHeader, Miniblock)> {
+    let mut to_exclusive = tip + 1;
+
+    while to_exclusive > 0 {
+        let from = to_exclusive.saturating_sub(MINIBLOCK_BATCH_SIZE);
+        let miniblocks = client.get_miniblocks(stream_id, from, to_exclusive).await?;
+
+        for miniblock in miniblocks {
+            if has_hash(&miniblock, miniblock_hash) {
+                let header = miniblock.decode_header()?;
+                return Ok((header, miniblock));
+            }
+        }
+
+        to_exclusive = from;
+    }
+
+    Err(TownsError::NotFound.into())
+}
+
+fn has_hash(miniblock: &Miniblock, miniblock_hash: &FixedBytes<32>) -> bool {
+    miniblock
+        .header
+        .as_ref()
+        .is_some_and(|header| header.hash.as_slice() == miniblock_hash.as_slice())
+}
+
+/// Number of the miniblock with the given hash if its registration is in the local index.
+fn registered_miniblock_num(
+    cfg: &config::Config,
+    stream_id: &StreamId,
+    miniblock_hash: &FixedBytes<32>,
+) -> eyre::Result<Option<u64>> {
+    let Some(index) = index::open(cfg)? else {
+        return Ok(None);
+    };
+    let Some(range) = index.covered(&(0..=u64::MAX))? else {
+        return Ok(None);
+    };
+
+    let id = stream_id.as_fixed_bytes32();
+    let events = index.stream_events(stream_id, range)?;
+    Ok(events.iter().find_map(|log| match &log.event {
+        RegistryEvent::MiniblockBatch(updates) => updates
+            .iter()
+            .find(|update| update.stream_id == id && update.last_miniblock_hash == *miniblock_hash)
+            .map(|update| update.last_miniblock_num),
+        _ => None,
+    }))
+}
+
+fn to_hash(raw: &[u8]) -> Option<FixedBytes<32>> {
+    (raw.len() == 32).then(|| FixedBytes::<32>::from_slice(raw))
+}
+
+/// Validate a consecutive range of miniblocks. It recomputes the miniblock and event hashes and
+/// checks that each miniblock links to the miniblock before it. The first miniblock must have
+/// number `first_num`, and if given, link to `prev_hash`. All problems are returned together with
+/// the number of the miniblock they were found in.
+pub(crate) fn validate_chain(
+    first_num: u64,
+    mut prev_hash: Option<FixedBytes<32>>,
+    miniblocks: &[Miniblock],
+) -> Vec<(u64, TownsError)> {
+    let mut problems = Vec::new();
+
+    for (expected_num, miniblock) in (first_num..).zip(miniblocks) {
+        let Some(envelope) = miniblock.header.as_ref() else {
+            problems.push((
+                expected_num,
+                TownsError::InvalidArgument("miniblock header"),
+            ));
+            prev_hash = None;
+            continue;
+        };
+
+        let computed_hash = envelope.compute_hash();
+        if envelope.hash.as_slice() != computed_hash.as_slice() {
+            problems.push((
+                expected_num,
+                TownsError::InvalidMiniblockHash(
+                    computed_hash,
+                    to_hash(&envelope.hash).unwrap_or_default(),
+                ),
+            ));
+        }
+
+        let header = match miniblock.decode_header() {
+            Ok(header) => header,
+            Err(err) => {
+                problems.push((expected_num, err));
+                prev_hash = Some(computed_hash);
+                continue;
+            }
+        };
+
+        if header.miniblock_num != expected_num as i64 {
+            problems.push((
+                expected_num,
+                TownsError::InvalidPreviousMiniblockNum(expected_num, header.miniblock_num as u64),
+            ));
+        }
+
+        if let Some(prev_hash) = prev_hash {
+            if header.prev_miniblock_hash.as_slice() != prev_hash.as_slice() {
+                problems.push((
+                    expected_num,
+                    TownsError::InvalidPreviousMiniblockHash(
+                        prev_hash,
+                        to_hash(&header.prev_miniblock_hash).unwrap_or_default(),
+                    ),
+                ));
+            }
+        }
+
+        if header.event_hashes.len() != miniblock.events.len() {
+            problems.push((
+                expected_num,
+                TownsError::InvalidArgumentWithValue(
+                    "miniblock event count",
+                    format!(
+                        "header lists {} events, miniblock contains {}",
+                        header.event_hashes.len(),
+                        miniblock.events.len()
+                    ),
+                ),
+            ));
+        }
+
+        for (listed_hash, event) in header.event_hashes.iter().zip(&miniblock.events) {
+            let computed_event_hash = event.compute_hash();
+            if listed_hash.as_slice() != computed_event_hash.as_slice()
+                || event.hash.as_slice() != computed_event_hash.as_slice()
+            {
+                problems.push((
+                    expected_num,
+                    TownsError::InvalidEventHash(
+                        computed_event_hash,
+                        to_hash(listed_hash).unwrap_or_default(),
+                    ),
+                ));
+            }
+        }
+
+        prev_hash = Some(computed_hash);
+    }
+
+    problems
+}
+
+#[derive(Serialize)]
+pub(crate) struct MiniblockEvent {
+    pub hash: Bytes,
+    pub creator: Bytes,
+    pub created_at_epoch_ms: i64,
+    pub payload: &'static str,
+}
+
+#[derive(Serialize)]
+pub(crate) struct MiniblockReport {
+    pub stream_id: StreamId,
+    pub node: Address,
+    pub miniblock_num: i64,
+    pub hash: Bytes,
+    pub prev_hash: Bytes,
+    pub timestamp: Option<String>,
+    pub event_num_offset: i64,
+    pub snapshot: bool,
+    pub events: Vec<MiniblockEvent>,
+}
+
+impl MiniblockReport {
+    fn new(
+        stream_id: StreamId,
+        node: Address,
+        header: MiniblockHeader,
+        miniblock: &Miniblock,
+    ) -> eyre::Result<Self> {
+        let events = miniblock
+            .events
+            .iter()
+            .map(|envelope| {
+                let event = envelope.decode_event()?;
+                Ok(MiniblockEvent {
+                    hash: Bytes::copy_from_slice(&envelope.hash),
+                    creator: event.creator_address.into(),
+                    created_at_epoch_ms: event.created_at_epoch_ms,
+                    payload: event
+                        .payload
+                        .as_ref()
+                        .map(|payload| payload.name())
+                        .unwrap_or("none"),
+                })
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+
+        Ok(MiniblockReport {
+            stream_id,
+            node,
+            miniblock_num: header.miniblock_num,
+            hash: miniblock
+                .header
+                .as_ref()
+                .map(|header| Bytes::copy_from_slice(&header.hash))
+                .unwrap_or_default(),
+            prev_hash: header.prev_miniblock_hash.into(),
+            timestamp: header
+                .timestamp
+                .map(|timestamp| format!("{}.{:09}", timestamp.seconds, timestamp.nanos)),
+            event_num_offset: header.event_num_offset,
+            snapshot: header.snapshot.is_some() || header.snapshot_hash.is_some(),
+            events,
+        })
+    }
+}
+
+impl Report for MiniblockReport {
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("stream", self.stream_id.to_string()),
+            ("node", self.node.to_string()),
+            ("miniblock", self.miniblock_num.to_string()),
+            ("hash", self.hash.to_string()),
+            ("prev hash", self.prev_hash.to_string()),
+            ("timestamp", output::opt(&self.timestamp)),
+            ("event offset", self.event_num_offset.to_string()),
+            ("snapshot", self.snapshot.to_string()),
+            ("events", self.events.len().to_string()),
+        ]
+    }
+
+    fn table(&self) -> Option<Table> {
+        Some(Table {
+            columns: ["hash", "creator", "created_at_epoch_ms", "payload"]
+                .map(String::from)
+                .to_vec(),
+            rows: self
+                .events
+                .iter()
+                .map(|event| {
+                    vec![
+                        event.hash.to_string(),
+                        event.creator.to_string(),
+                        event.created_at_epoch_ms.to_string(),
+                        event.payload.to_string(),
+                    ]
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Get miniblock by hash from one of the nodes the stream is placed on. The miniblock is fetched
+/// directly if its number is given or registered in the local index, otherwise the node is
+/// searched backwards from the stream tip.
+pub(crate) async fn get(
+    cfg: &config::Config,
+    stream_id: StreamId,
+    miniblock_hash: FixedBytes<32>,
+    miniblock_num: Option<u64>,
+) -> eyre::Result<MiniblockReport> {
+    let client = cfg.registry_client()?;
+    let block = BlockId::Number(BlockNumberOrTag::Number(cfg.river_block(&client).await?));
+
+    let (stream, nodes) = client
+        .stream_with_nodes(&stream_id, block)
+        .await
+        .wrap_err("Failed to get stream nodes")?;
+    let nodes = nodes.into_iter().map(|node| (node.nodeAddress, node.url));
+
+    let miniblock_num = match miniblock_num {
+        Some(miniblock_num) => Some(miniblock_num),
+        None => registered_miniblock_num(cfg, &stream_id, &miniblock_hash)?,
+    };
+    if miniblock_num.is_none() {
+        eprintln!(
+            "searching backwards from miniblock {}, pass --miniblock-num to fetch the miniblock directly",
+            stream.lastMiniblockNum
+        );
+    }
+
+    for (node_address, url) in nodes {
+        let node_client = NodeClient::new(&url);
+        let found = match miniblock_num {
+            Some(miniblock_num) => {
+                fetch_miniblock(&node_client, &stream_id, miniblock_num, &miniblock_hash).await
+            }
+            None => {
+                find_miniblock(&node_client, &stream_id, stream.lastMiniblockNum, &miniblock_hash)
+                    .await
+            }
+        };
+        match found {
+            Ok((header, miniblock)) => {
+                return MiniblockReport::new(stream_id, node_address, header, &miniblock);
+            }
+            Err(err) => eprintln!("node {} ({}): {:#}", node_address, url, err),
+        }
+    }
+
+    Err(TownsError::NotFound.into())
+}
+
+#[derive(Serialize)]
+pub(crate) struct ValidationProblem {
+    pub miniblock_num: u64,
+    #[serde(serialize_with = "output::display")]
+    pub problem: TownsError,
+}
+
+#[derive(Serialize)]
+pub(crate) struct Validation {
+    pub stream_id: StreamId,
+    pub node: Address,
+    pub url: String,
+    pub from: u64,
+    pub to: u64,
+    pub registry_tip_num: u64,
+    pub registry_tip_hash: FixedBytes<32>,
+    pub node_tip_num: u64,
+    pub node_tip_hash: FixedBytes<32>,
+    pub validated: usize,
+    pub problems: Vec<ValidationProblem>,
+}
+
+impl Validation {
+    /// Turn the validation into an error when problems were found.
+    pub(crate) fn into_result(self) -> eyre::Result<()> {
+        if self.problems.is_empty() {
+            return Ok(());
+        }
+
+        Err(eyre::eyre!(
+            "found {} problem(s) in {} miniblocks",
+            self.problems.len(),
+            self.validated
+        ))
+    }
+}
+
+impl Report for Validation {
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("stream", self.stream_id.to_string()),
+            ("node", format!("{} ({})", self.node, self.url)),
+            ("range", format!("{}..={}", self.from, self.to)),
+            (
+                "registry tip",
+                format!("#{} {}", self.registry_tip_num, self.registry_tip_hash),
+            ),
+            (
+                "node tip",
+                format!("#{} {}", self.node_tip_num, self.node_tip_hash),
+            ),
+            ("validated", self.validated.to_string()),
+            ("problems", self.problems.len().to_string()),
+        ]
+    }
+
+    fn table(&self) -> Option<Table> {
+        Some(Table {
+            columns: ["miniblock_num", "problem"].map(String::from).to_vec(),
+            rows: self
+                .problems
+                .iter()
+                .map(|problem| {
+                    vec![
+                        problem.miniblock_num.to_string(),
+                        problem.problem.to_string(),
+                    ]
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Validate the hash chain of a range of miniblocks and compare the node tip with the registry
+pub(crate) async fn validate(
+    cfg: &config::Config,
+    stream_id: StreamId,
+    from: Option<u64>,
+    to: Option<u64>,
+    node: Option<Address>,
+) -> eyre::Result<Validation> {
+    let client = cfg.registry_client()?;
+    let block = BlockId::Number(BlockNumberOrTag::Number(cfg.river_block(&client).await?));
+
+    let (stream, nodes) = client
+        .stream_with_nodes(&stream_id, block)
+        .await
+        .wrap_err("Failed to get stream nodes")?;
+    let nodes = nodes.into_iter().map(|node| (node.nodeAddress, node.url));
+    let (node_address, url) = match node {
+        Some(node) => nodes.clone().find(|(address, _)| *address == node).ok_or(
+            TownsError::InvalidArgumentWithValue("node", format!("stream not placed on {}", node)),
+        )?,
+        None => nodes
+            .clone()
+            .next()
+            .ok_or(TownsError::InvalidArgument("stream without nodes"))?,
+    };
+
+    let node_client = NodeClient::new(&url);
+    let (node_tip_num, node_tip_hash) = node_client.get_last_miniblock_hash(&stream_id).await?;
+
+    let from = from.unwrap_or(0);
+    let to = to.unwrap_or(stream.lastMiniblockNum);
+    if from > to {
+        return Err(TownsError::InvalidArgumentWithValue(
+            "range",
+            format!("from {} is after to {}", from, to),
+        )
+        .into());
+    }
+
+    // the node takes signed miniblock numbers
+    let to_exclusive = to
+        .checked_add(1)
+        .filter(|to_exclusive| i64::try_from(*to_exclusive).is_ok())
+        .ok_or_else(|| {
+            TownsError::InvalidArgumentWithValue("to", format!("miniblock {} is out of range", to))
+        })?;
+
+    // include the miniblock before the range so the first miniblock in the range can be linked
+    let first = from.saturating_sub(1);
+    let miniblocks = node_client
+        .get_miniblocks(&stream_id, first, to_exclusive)
+        .await?;
+    if miniblocks.len() as u64 != to_exclusive - first {
+        return Err(TownsError::InvalidArgumentWithValue(
+            "range",
+            format!(
+                "node returned {} miniblocks for [{}, {}]",
+                miniblocks.len(),
+                first,
+                to
+            ),
+        )
+        .into());
+    }
+
+    let (first_num, prev_hash, range) = if from == 0 {
+        (0, None, miniblocks.as_slice())
+    } else {
+        let anchor = miniblocks[0]
+            .header
+            .as_ref()
+            .map(|header| header.compute_hash());
+        (from, anchor, &miniblocks[1..])
+    };
+
+    let mut problems = validate_chain(first_num, prev_hash, range);
+
+    if stream.lastMiniblockNum != node_tip_num {
+        problems.push((
+            node_tip_num,
+            TownsError::InvalidPreviousMiniblockNum(stream.lastMiniblockNum, node_tip_num),
+        ));
+    } else if stream.lastMiniblockHash != node_tip_hash {
+        problems.push((
+            node_tip_num,
+            TownsError::InvalidPreviousMiniblockHash(stream.lastMiniblockHash, node_tip_hash),
+        ));
+    }
+
+    Ok(Validation {
+        stream_id,
+        node: node_address,
+        url,
+        from,
+        to,
+        registry_tip_num: stream.lastMiniblockNum,
+        registry_tip_hash: stream.lastMiniblockHash,
+        node_tip_num,
+        node_tip_hash,
+        validated: range.len(),
+        problems: problems
+            .into_iter()
+            .map(|(miniblock_num, problem)| ValidationProblem {
+                miniblock_num,
+                problem,
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Bytes, extract::State, routing::post};
+    use std::sync::Arc;
+    use towns_protocol_types::protocol::{
+        Envelope, StreamEvent, river_hash, stream_event::Payload,
+    };
+
+    /// Maximum number of miniblocks the stand-in node returns per call.
+    const NODE_PAGE_SIZE: usize = 30;
+
+    fn fixture_miniblocks(count: u64) -> Vec<Miniblock> {
+        let mut prev_hash = Vec::new();
+        (0..count)
+            .map(|num| {
+                let member_event = StreamEvent {
+                    created_at_epoch_ms: 1_700_000_000_000 + num as i64,
+                    payload: Some(Payload::MemberPayload(vec![num as u8])),
+                    ..Default::default()
+                }
+                .encode_to_vec();
+                let member_event_hash = river_hash(&member_event).to_vec();
+
+                let header_event = StreamEvent {
+                    created_at_epoch_ms: 1_700_000_000_000 + num as i64,
+                    payload: Some(Payload::MiniblockHeader(MiniblockHeader {
+                        miniblock_num: num as i64,
+                        prev_miniblock_hash: prev_hash.clone(),
+                        event_hashes: vec![member_event_hash.clone()],
+                        ..Default::default()
+                    })),
+                    ..Default::default()
+                }
+                .encode_to_vec();
+                let hash = river_hash(&header_event).to_vec();
+                prev_hash = hash.clone();
+
+                Miniblock {
+                    events: vec![Envelope {
+                        hash: member_event_hash,
+                        signature: vec![],
+                        event: member_event,
+                    }],
+                    header: Some(Envelope {
+                        hash,
+                        signature: vec![],
+                        event: header_event,
+                    }),
+                }
+            })
+            .collect()
+    }
+
+    async fn get_miniblocks(State(miniblocks): State<Arc<Vec<Miniblock>>>, body: Bytes) -> Vec<u8> {
+        let request = GetMiniblocksRequest::decode(body).unwrap();
+        let from = (request.from_inclusive as usize).min(miniblocks.len());
+        let to = (request.to_exclusive as usize)
+            .min(miniblocks.len())
+            .min(from + NODE_PAGE_SIZE);
+
+        GetMiniblocksResponse {
+            miniblocks: miniblocks[from..to].to_vec(),
+            terminus: from == 0,
+        }
+        .encode_to_vec()
+    }
+
+    async fn stand_in_node(miniblocks: Vec<Miniblock>) -> String {
+        let app = Router::new()
+            .route("/river.StreamService/GetMiniblocks", post(get_miniblocks))
+            .with_state(Arc::new(miniblocks));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}/", addr)
+    }
+
+    fn stream_id() -> StreamId {
+        StreamId::try_from("2001000000000000000000000000000000000000000000000000000000000009")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn get_miniblock_range() {
+        let miniblocks = fixture_miniblocks(250);
+        let client = NodeClient::new(&stand_in_node(miniblocks.clone()).await);
+
+        let got = client.get_miniblocks(&stream_id(), 10, 110).await.unwrap();
+        assert_eq!(&miniblocks[10..110], got.as_slice());
+    }
+
+    #[tokio::test]
+    async fn find_miniblock_by_hash() {
+        let miniblocks = fixture_miniblocks(250);
+        let client = NodeClient::new(&stand_in_node(miniblocks.clone()).await);
+
+        let hash = FixedBytes::<32>::from_slice(&miniblocks[42].header.as_ref().unwrap().hash);
+        let (header, miniblock) = find_miniblock(&client, &stream_id(), 249, &hash)
+            .await
+            .unwrap();
+
+        assert_eq!(42, header.miniblock_num);
+        assert_eq!(miniblocks[42], miniblock);
+    }
+
+    #[tokio::test]
+    async fn fetch_miniblock_by_num() {
+        let miniblocks = fixture_miniblocks(250);
+        let client = NodeClient::new(&stand_in_node(miniblocks.clone()).await);
+
+        let hash = FixedBytes::<32>::from_slice(&miniblocks[42].header.as_ref().unwrap().hash);
+        let (header, miniblock) = fetch_miniblock(&client, &stream_id(), 42, &hash)
+            .await
+            .unwrap();
+        assert_eq!(42, header.miniblock_num);
+        assert_eq!(miniblocks[42], miniblock);
+
+        // the miniblock with the number must have the hash
+        let err = fetch_miniblock(&client, &stream_id(), 43, &hash)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TownsError>(),
+            Some(TownsError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn find_unknown_miniblock() {
+        let client = NodeClient::new(&stand_in_node(fixture_miniblocks(250)).await);
+
+        let err = find_miniblock(&client, &stream_id(), 249, &FixedBytes::ZERO)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<TownsError>(),
+            Some(TownsError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn validate_valid_chain() {
+        let miniblocks = fixture_miniblocks(20);
+        assert!(validate_chain(0, None, &miniblocks).is_empty());
+
+        let anchor = miniblocks[4].header.as_ref().unwrap().compute_hash();
+        assert!(validate_chain(5, Some(anchor), &miniblocks[5..]).is_empty());
+    }
+
+    #[test]
+    fn validate_broken_link() {
+        let mut miniblocks = fixture_miniblocks(20);
+        miniblocks.remove(7);
+
+        let problems = validate_chain(0, None, &miniblocks);
+        assert!(
+            problems.iter().any(|(num, err)| *num == 7
+                && matches!(err, TownsError::InvalidPreviousMiniblockHash(_, _)))
+        );
+        assert!(
+            problems.iter().any(|(num, err)| *num == 7
+                && matches!(err, TownsError::InvalidPreviousMiniblockNum(7, 8)))
+        );
+    }
+
+    #[test]
+    fn validate_tampered_miniblock() {
+        let mut miniblocks = fixture_miniblocks(20);
+        miniblocks[3].events[0].event.push(0);
+        miniblocks[9].header.as_mut().unwrap().hash[0] ^= 0xff;
+
+        let problems = validate_chain(0, None, &miniblocks);
+        assert!(
+            problems
+                .iter()
+                .any(|(num, err)| *num == 3 && matches!(err, TownsError::InvalidEventHash(_, _)))
+        );
+        assert!(
+            problems.iter().any(
+                |(num, err)| *num == 9 && matches!(err, TownsError::InvalidMiniblockHash(_, _))
+            )
+        );
+    }
+}
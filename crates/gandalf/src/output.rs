@@ -0,0 +1,343 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::ValueEnum;
+use serde::{Serialize, Serializer};
+use std::fmt::Display;
+use std::io::Write;
+use std::ops::RangeInclusive;
+
+/// Format in which command results are written to stdout.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+/// Rows with the same columns.
+pub(crate) struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Report is implemented by command results. The JSON output is derived from the serde
+/// representation, table and csv output are derived from the fields and table.
+pub(crate) trait Report: Serialize {
+    /// Key/value pairs that describe the result as a whole.
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![]
+    }
+
+    /// Tabular part of the result.
+    fn table(&self) -> Option<Table> {
+        None
+    }
+}
+
+/// Render the report in the given format to stdout.
+pub(crate) fn render<R: Report>(format: OutputFormat, report: &R) -> eyre::Result<()> {
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+
+    match format {
+        OutputFormat::Table => render_table(&mut out, report)?,
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, report)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Csv => render_csv(&mut out, report)?,
+    }
+
+    Ok(())
+}
+
+fn render_table<R: Report>(out: &mut impl Write, report: &R) -> eyre::Result<()> {
+    let fields = report.fields();
+    let key_width = fields.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+    for (key, value) in &fields {
+        writeln!(out, "{:>key_width$}: {}", key, value)?;
+    }
+
+    if let Some(table) = report.table() {
+        if !fields.is_empty() {
+            writeln!(out)?;
+        }
+
+        let mut widths: Vec<usize> = table.columns.iter().map(|column| column.len()).collect();
+        for row in &table.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+
+        write_row(out, &widths, &table.columns)?;
+        for row in &table.rows {
+            write_row(out, &widths, row)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_row(out: &mut impl Write, widths: &[usize], cells: &[String]) -> eyre::Result<()> {
+    let line = cells
+        .iter()
+        .zip(widths)
+        .map(|(cell, width)| format!("{:<width$}", cell))
+        .collect::<Vec<_>>()
+        .join("  ");
+    writeln!(out, "{}", line.trim_end())?;
+    Ok(())
+}
+
+/// Write the table if the report has one, otherwise the fields as a single row.
+fn render_csv<R: Report>(out: &mut impl Write, report: &R) -> eyre::Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+
+    match report.table() {
+        Some(table) => {
+            writer.write_record(&table.columns)?;
+            for row in &table.rows {
+                writer.write_record(row)?;
+            }
+        }
+        None => {
+            let fields = report.fields();
+            writer.write_record(fields.iter().map(|(key, _)| key))?;
+            writer.write_record(fields.iter().map(|(_, value)| value))?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes rows to stdout as they become available, for commands that follow the chain. JSON
+/// output is written as one object per line.
+pub(crate) struct RowWriter {
+    format: OutputFormat,
+    columns: Vec<String>,
+    widths: Vec<usize>,
+    header_written: bool,
+}
+
+impl RowWriter {
+    pub(crate) fn new(format: OutputFormat, columns: &[&str]) -> Self {
+        RowWriter {
+            format,
+            columns: columns.iter().map(|column| column.to_string()).collect(),
+            widths: columns.iter().map(|column| column.len()).collect(),
+            header_written: false,
+        }
+    }
+
+    /// Write a single row, `item` is used for JSON output and `row` for table and csv output.
+    pub(crate) fn write<T: Serialize>(&mut self, item: &T, row: &[String]) -> eyre::Result<()> {
+        let stdout = std::io::stdout();
+        let mut out = stdout.lock();
+        self.write_to(&mut out, item, row)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    fn write_to<T: Serialize>(
+        &mut self,
+        out: &mut impl Write,
+        item: &T,
+        row: &[String],
+    ) -> eyre::Result<()> {
+        match self.format {
+            OutputFormat::Table => {
+                for (width, cell) in self.widths.iter_mut().zip(row) {
+                    *width = (*width).max(cell.len());
+                }
+                if !self.header_written {
+                    write_row(out, &self.widths, &self.columns)?;
+                }
+                write_row(out, &self.widths, row)?;
+            }
+            OutputFormat::Json => {
+                serde_json::to_writer(&mut *out, item)?;
+                writeln!(out)?;
+            }
+            OutputFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(out);
+                if !self.header_written {
+                    writer.write_record(&self.columns)?;
+                }
+                writer.write_record(row)?;
+                writer.flush()?;
+            }
+        }
+
+        self.header_written = true;
+        Ok(())
+    }
+}
+
+/// Serialize a value through its Display implementation.
+pub(crate) fn display<T: Display, S: Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+/// Render an optional value for table and csv output, none is rendered as an empty cell.
+pub(crate) fn opt<T: Display>(value: &Option<T>) -> String {
+    value.as_ref().map(ToString::to_string).unwrap_or_default()
+}
+
+/// Render a wall-clock time for table and csv output, e.g. 2025-01-01T12:00:00Z.
+pub(crate) fn time(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Render a list of values for table and csv output.
+pub(crate) fn list<T: Display>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// Reports the progress of a scan over a block range on stderr.
+pub(crate) struct Progress {
+    label: &'static str,
+    range: RangeInclusive<u64>,
+    events: usize,
+    /// name of a position in the range and of the counted items
+    units: (&'static str, &'static str),
+}
+
+impl Progress {
+    pub(crate) fn new(label: &'static str, range: RangeInclusive<u64>) -> Self {
+        Progress {
+            label,
+            range,
+            events: 0,
+            units: ("block", "events"),
+        }
+    }
+
+    /// Report the progress of a scan over something else than blocks, e.g. the registry stream
+    /// list.
+    pub(crate) fn with_units(mut self, position: &'static str, items: &'static str) -> Self {
+        self.units = (position, items);
+        self
+    }
+
+    /// Report that all blocks up to the end of `scanned` are processed.
+    pub(crate) fn advance(&mut self, scanned: &RangeInclusive<u64>, events: usize) {
+        self.events += events;
+        eprintln!("{}", self.line(*scanned.end()));
+    }
+
+    fn line(&self, block: u64) -> String {
+        let (from, to) = (*self.range.start(), *self.range.end());
+        let total = to.saturating_sub(from) + 1;
+        let done = block.saturating_sub(from).saturating_add(1).min(total);
+        format!(
+            "{}: {} {} / {} ({}%), {} {}",
+            self.label,
+            self.units.0,
+            block,
+            to,
+            done * 100 / total,
+            self.events,
+            self.units.1
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Fixture {
+        river_block: u64,
+        rows: Vec<(u64, &'static str)>,
+    }
+
+    impl Report for Fixture {
+        fn fields(&self) -> Vec<(&'static str, String)> {
+            vec![("river block", self.river_block.to_string())]
+        }
+
+        fn table(&self) -> Option<Table> {
+            Some(Table {
+                columns: vec!["count".to_string(), "name".to_string()],
+                rows: self
+                    .rows
+                    .iter()
+                    .map(|(count, name)| vec![count.to_string(), name.to_string()])
+                    .collect(),
+            })
+        }
+    }
+
+    fn fixture() -> Fixture {
+        Fixture {
+            river_block: 42,
+            rows: vec![(1, "a,b"), (1000, "c")],
+        }
+    }
+
+    #[test]
+    fn render_fixture_table() {
+        let mut out = Vec::new();
+        render_table(&mut out, &fixture()).unwrap();
+        assert_eq!(
+            "river block: 42\n\ncount  name\n1      a,b\n1000   c\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn render_fixture_csv() {
+        let mut out = Vec::new();
+        render_csv(&mut out, &fixture()).unwrap();
+        assert_eq!(
+            "count,name\n1,\"a,b\"\n1000,c\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn write_rows() {
+        let rows = [(1u64, "a,b"), (1000, "c")];
+        let row = |(count, name): &(u64, &str)| vec![count.to_string(), name.to_string()];
+
+        let mut out = Vec::new();
+        let mut writer = RowWriter::new(OutputFormat::Csv, &["count", "name"]);
+        for r in &rows {
+            writer.write_to(&mut out, r, &row(r)).unwrap();
+        }
+        assert_eq!(
+            "count,name\n1,\"a,b\"\n1000,c\n",
+            String::from_utf8(out).unwrap()
+        );
+
+        let mut out = Vec::new();
+        let mut writer = RowWriter::new(OutputFormat::Json, &["count", "name"]);
+        for r in &rows {
+            writer.write_to(&mut out, r, &row(r)).unwrap();
+        }
+        assert_eq!(
+            "[1,\"a,b\"]\n[1000,\"c\"]\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn progress_line() {
+        let mut progress = Progress::new("scan", 100..=299);
+        progress.events = 7;
+        assert_eq!("scan: block 149 / 299 (25%), 7 events", progress.line(149));
+        assert_eq!("scan: block 299 / 299 (100%), 7 events", progress.line(299));
+
+        let progress = Progress::new("list", 0..=99).with_units("stream", "matched");
+        assert_eq!("list: stream 49 / 99 (50%), 0 matched", progress.line(49));
+    }
+}
@@ -0,0 +1,252 @@
+use alloy_provider::Provider;
+use alloy_rpc_types::BlockNumberOrTag;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeDelta, Utc};
+use eyre::WrapErr;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use towns_protocol_registry::RegistryClient;
+use towns_protocol_types::TownsError;
+
+/// River block that all queries of a command are pinned to, so the command sees a single
+/// consistent view of the registry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Snapshot {
+    Latest,
+    Finalized,
+    Safe,
+    Block(u64),
+    /// latest block with a timestamp at or before the given time
+    At(DateTime<Utc>),
+}
+
+impl Snapshot {
+    /// Resolve the snapshot to a river block number.
+    pub(crate) async fn resolve<P: Provider>(
+        &self,
+        client: &RegistryClient<P>,
+    ) -> eyre::Result<u64> {
+        let block = match self {
+            Snapshot::Latest => client.block_number().await?,
+            Snapshot::Finalized => {
+                client
+                    .block_number_by_tag(BlockNumberOrTag::Finalized)
+                    .await?
+            }
+            Snapshot::Safe => client.block_number_by_tag(BlockNumberOrTag::Safe).await?,
+            Snapshot::Block(block) => *block,
+            Snapshot::At(time) => {
+                let timestamp = u64::try_from(time.timestamp())
+                    .map_err(|_| TownsError::InvalidArgumentWithValue("time", time.to_rfc3339()))?;
+                let head = client.block_number().await?;
+                client.block_at(timestamp, head).await.wrap_err_with(|| {
+                    format!("no river block at or before {}", time.to_rfc3339())
+                })?
+            }
+        };
+        Ok(block)
+    }
+}
+
+impl FromStr for Snapshot {
+    type Err = TownsError;
+
+    /// Parse a block number or one of the tags latest, finalized and safe.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "latest" => Ok(Snapshot::Latest),
+            "finalized" => Ok(Snapshot::Finalized),
+            "safe" => Ok(Snapshot::Safe),
+            _ => s
+                .parse()
+                .map(Snapshot::Block)
+                .map_err(|_| TownsError::InvalidArgumentWithValue("block", s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Snapshot::Latest => f.write_str("latest"),
+            Snapshot::Finalized => f.write_str("finalized"),
+            Snapshot::Safe => f.write_str("safe"),
+            Snapshot::Block(block) => write!(f, "{}", block),
+            Snapshot::At(time) => f.write_str(&time.to_rfc3339()),
+        }
+    }
+}
+
+/// Parse an RFC3339 time, e.g. 2025-01-01T12:00:00Z. Seconds and the time itself can be left
+/// out, e.g. 2025-01-01T12:00Z or 2025-01-01, in which case the time is in UTC.
+pub(crate) fn parse_time(s: &str) -> Result<DateTime<Utc>, TownsError> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    if let Ok(time) = NaiveDateTime::parse_from_str(s.trim_end_matches('Z'), "%Y-%m-%dT%H:%M") {
+        return Ok(time.and_utc());
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc());
+    }
+    Err(TownsError::InvalidArgumentWithValue("time", s.to_string()))
+}
+
+/// Start of a block range, either a duration before the end of the range, e.g. 6h, or a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Since {
+    Ago(TimeDelta),
+    Time(DateTime<Utc>),
+}
+
+impl Since {
+    /// First river block in `..=to` with a timestamp at or after the start of the range. A
+    /// duration is measured from the timestamp of `to`, not the current time, so it stays
+    /// consistent with the pinned river block. Fails if the start is after the timestamp of `to`,
+    /// the range would be empty.
+    pub(crate) async fn first_block<P: Provider>(
+        &self,
+        client: &RegistryClient<P>,
+        to: u64,
+    ) -> eyre::Result<u64> {
+        let start = match self {
+            Since::Ago(duration) => {
+                let to_time = client
+                    .block_timestamp(to)
+                    .await
+                    .wrap_err_with(|| format!("Failed to get timestamp of river block {}", to))?;
+                to_time.saturating_sub(duration.num_seconds().unsigned_abs())
+            }
+            Since::Time(time) => u64::try_from(time.timestamp()).unwrap_or_default(),
+        };
+
+        // the block after the last block before the start of the range
+        match client.block_at(start.saturating_sub(1), to).await {
+            Ok(block) if block >= to => Err(eyre::eyre!(
+                "{} is after river block {}, the last block of the range",
+                self,
+                to
+            )),
+            Ok(block) => Ok(block + 1),
+            Err(TownsError::NotFound) => Ok(0),
+            Err(err) => {
+                Err(err).wrap_err_with(|| format!("Failed to find river block at {}", self))
+            }
+        }
+    }
+}
+
+impl FromStr for Since {
+    type Err = TownsError;
+
+    /// Parse a duration such as 90s, 30m, 6h, 2d or 1w, or a time accepted by [`parse_time`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match parse_duration(s) {
+            Some(duration) => Ok(Since::Ago(duration)),
+            None => parse_time(s)
+                .map(Since::Time)
+                .map_err(|_| TownsError::InvalidArgumentWithValue("since", s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Since {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Since::Ago(duration) => write!(f, "{}s ago", duration.num_seconds()),
+            Since::Time(time) => f.write_str(&time.to_rfc3339()),
+        }
+    }
+}
+
+/// Parse a whole number followed by one of the units s, m, h, d and w.
+fn parse_duration(s: &str) -> Option<TimeDelta> {
+    let unit = s.chars().last()?;
+    let count: i64 = s[..s.len() - unit.len_utf8()].parse().ok()?;
+    match unit {
+        's' => TimeDelta::try_seconds(count),
+        'm' => TimeDelta::try_minutes(count),
+        'h' => TimeDelta::try_hours(count),
+        'd' => TimeDelta::try_days(count),
+        'w' => TimeDelta::try_weeks(count),
+        _ => None,
+    }
+    .filter(|duration| *duration >= TimeDelta::zero())
+}
+
+/// Wall-clock time of a river block.
+pub(crate) async fn block_time<P: Provider>(
+    client: &RegistryClient<P>,
+    block: u64,
+) -> eyre::Result<DateTime<Utc>> {
+    let timestamp = client
+        .block_timestamp(block)
+        .await
+        .wrap_err_with(|| format!("Failed to get timestamp of river block {}", block))?;
+    Ok(to_time(timestamp))
+}
+
+/// Wall-clock times of the given river blocks.
+pub(crate) async fn block_times<P: Provider>(
+    client: &RegistryClient<P>,
+    blocks: impl IntoIterator<Item = u64>,
+) -> eyre::Result<HashMap<u64, DateTime<Utc>>> {
+    let timestamps = client
+        .block_timestamps(blocks)
+        .await
+        .wrap_err("Failed to get river block timestamps")?;
+    Ok(timestamps
+        .into_iter()
+        .map(|(block, timestamp)| (block, to_time(timestamp)))
+        .collect())
+}
+
+pub(crate) fn to_time(timestamp: u64) -> DateTime<Utc> {
+    i64::try_from(timestamp)
+        .ok()
+        .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_snapshot() {
+        assert_eq!(Snapshot::Latest, "latest".parse().unwrap());
+        assert_eq!(Snapshot::Finalized, "finalized".parse().unwrap());
+        assert_eq!(Snapshot::Safe, "safe".parse().unwrap());
+        assert_eq!(Snapshot::Block(42), "42".parse().unwrap());
+        assert!("pending".parse::<Snapshot>().is_err());
+    }
+
+    #[test]
+    fn parse_times() {
+        let expected = DateTime::parse_from_rfc3339("2025-01-01T12:30:00Z").unwrap();
+        assert_eq!(expected, parse_time("2025-01-01T12:30:00Z").unwrap());
+        assert_eq!(expected, parse_time("2025-01-01T14:30:00+02:00").unwrap());
+        assert_eq!(expected, parse_time("2025-01-01T12:30Z").unwrap());
+        assert_eq!(
+            DateTime::parse_from_rfc3339("2025-01-01T00:00:00Z").unwrap(),
+            parse_time("2025-01-01").unwrap()
+        );
+        assert!(parse_time("yesterday").is_err());
+    }
+
+    #[test]
+    fn parse_since() {
+        assert_eq!(Since::Ago(TimeDelta::hours(6)), "6h".parse().unwrap());
+        assert_eq!(Since::Ago(TimeDelta::seconds(90)), "90s".parse().unwrap());
+        assert_eq!(Since::Ago(TimeDelta::minutes(30)), "30m".parse().unwrap());
+        assert_eq!(Since::Ago(TimeDelta::days(2)), "2d".parse().unwrap());
+        assert_eq!(Since::Ago(TimeDelta::weeks(1)), "1w".parse().unwrap());
+        assert_eq!(
+            Since::Time(parse_time("2025-01-01T00:00Z").unwrap()),
+            "2025-01-01T00:00Z".parse().unwrap()
+        );
+        assert!("6".parse::<Since>().is_err());
+        assert!("-6h".parse::<Since>().is_err());
+        assert!("6y".parse::<Since>().is_err());
+    }
+}
@@ -1,525 +1,712 @@
-use crate::config;
+use crate::{config, index, snapshot};
+use crate::output::{self, Report, Table};
+use crate::index::Selection;
+use crate::snapshot::Since;
 use alloy_primitives::{Address, Bytes, FixedBytes};
 use alloy_provider::Provider;
-use alloy_rpc_types::{BlockId, BlockNumberOrTag, Filter, Log};
-use alloy_sol_types::{SolEvent, SolType};
+use alloy_rpc_types::{BlockId, BlockNumberOrTag};
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
 use eyre::WrapErr;
-use towns_protocol_contracts::{
-    SetMiniblockArray, StreamEventType, StreamState, StreamsRegistry::{self, StreamUpdated}
-};
-use towns_protocol_types::{StreamId, TownsError};
-use std::{cmp::max, collections::{BTreeMap, HashSet}};
-
-/// Get stream inception event
-pub(crate) async fn inception(cfg: &config::Config, stream_id: StreamId) -> eyre::Result<()> {
-    let provider = cfg
-        .river_chain_provider()
-        .wrap_err("Invalid River chain RPC URL")?;
-    let streams_registry = StreamsRegistry::new(cfg.registry.address, &provider);
-    let low = cfg.registry.deployment_block;
-    let high = BlockId::Number(BlockNumberOrTag::Number(
-        provider
-            .get_block_number()
-            .await
-            .wrap_err("Failed to get block number")?,
-    ));
+use futures::TryStreamExt;
+use towns_protocol_registry::{self as registry, RegistryClient, RegistryEvent, RegistryLog, StreamFlags, StreamMeta, StreamsRegistry};
+use towns_protocol_types::{StreamId, StreamKind};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+#[derive(Serialize)]
+pub(crate) struct Inception {
+    pub stream_id: StreamId,
+    pub river_block: u64,
+    pub time: DateTime<Utc>,
+    pub block_hash: FixedBytes<32>,
+    pub transaction: FixedBytes<32>,
+    pub nodes: Vec<Address>,
+    pub genesis_hash: FixedBytes<32>,
+    pub genesis_miniblock: Option<Bytes>,
+}
+
+impl Inception {
+    fn new(inception: registry::Inception, time: DateTime<Utc>) -> Self {
+        Inception {
+            stream_id: inception.stream_id,
+            river_block: inception.river_block,
+            time,
+            block_hash: inception.block_hash,
+            transaction: inception.transaction,
+            nodes: inception.nodes,
+            genesis_hash: inception.genesis_hash,
+            genesis_miniblock: inception.genesis_miniblock,
+        }
+    }
+}
 
-    // binary search for the stream inception block
-    let mut low = low.as_u64().unwrap();
-    let mut high = high.as_u64().unwrap();
+impl Report for Inception {
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("stream", self.stream_id.to_string()),
+            ("river block", self.river_block.to_string()),
+            ("time", output::time(&self.time)),
+            ("block hash", self.block_hash.to_string()),
+            ("transaction", self.transaction.to_string()),
+            ("initial nodes", output::list(&self.nodes)),
+            ("genesis hash", self.genesis_hash.to_string()),
+            ("genesis miniblock", output::opt(&self.genesis_miniblock)),
+        ]
+    }
+}
 
-    loop {
-        if low > high {
-            break;
+#[derive(Serialize)]
+pub(crate) struct StreamDetails {
+    pub stream_id: StreamId,
+    pub miniblock_num: u64,
+    pub miniblock_hash: FixedBytes<32>,
+    pub nodes: Vec<Address>,
+    pub replication_factor: u64,
+    pub sealed: bool,
+    #[serde(serialize_with = "output::display")]
+    pub flags: StreamFlags,
+    #[serde(serialize_with = "output::display")]
+    pub reserved0: StreamMeta,
+    pub river_block: u64,
+    pub time: DateTime<Utc>,
+}
+
+impl Report for StreamDetails {
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("stream", self.stream_id.to_string()),
+            ("miniblock", self.miniblock_num.to_string()),
+            ("hash", self.miniblock_hash.to_string()),
+            ("nodes", output::list(&self.nodes)),
+            ("repl factor", self.replication_factor.to_string()),
+            ("sealed", self.sealed.to_string()),
+            ("flags", format!("{:#x} ({})", self.flags.bits(), self.flags)),
+            ("reserved0", format!("{:#x} ({})", self.reserved0.bits(), self.reserved0)),
+            ("river block", self.river_block.to_string()),
+            ("time", output::time(&self.time)),
+        ]
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct StreamCount {
+    pub streams: u64,
+    pub river_block: u64,
+}
+
+impl Report for StreamCount {
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("streams", self.streams.to_string()),
+            ("river block", self.river_block.to_string()),
+        ]
+    }
+}
+
+/// Stream update as found in the stream registry logs. Fields that don't apply to the event are
+/// left empty.
+#[derive(Serialize)]
+pub(crate) struct StreamUpdate {
+    pub event: &'static str,
+    pub river_block: u64,
+    /// wall-clock time of the river block, left empty when it isn't resolved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<DateTime<Utc>>,
+    pub transaction: FixedBytes<32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub miniblock_num: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub miniblock_hash: Option<FixedBytes<32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nodes: Option<Vec<Address>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replication_factor: Option<u64>,
+}
+
+impl From<RegistryLog> for StreamUpdate {
+    fn from(log: RegistryLog) -> Self {
+        let mut result = StreamUpdate {
+            event: log.event.name(),
+            river_block: log.block_number,
+            time: None,
+            transaction: log.transaction_hash,
+            miniblock_num: None,
+            miniblock_hash: None,
+            nodes: None,
+            replication_factor: None,
+        };
+
+        match log.event {
+            RegistryEvent::PlacementUpdated {
+                nodes,
+                replication_factor,
+                ..
+            } => {
+                result.nodes = Some(nodes);
+                result.replication_factor = Some(replication_factor);
+            }
+            RegistryEvent::NodePlacementUpdated { node, .. } => {
+                result.nodes = Some(vec![node]);
+            }
+            RegistryEvent::MiniblockBatch(updates) => {
+                if let Some(update) = updates.last() {
+                    result.miniblock_num = Some(update.last_miniblock_num);
+                    result.miniblock_hash = Some(update.last_miniblock_hash);
+                }
+            }
+            RegistryEvent::MiniblockUpdateFailed {
+                last_miniblock_hash,
+                last_miniblock_num,
+                ..
+            } => {
+                result.miniblock_num = Some(last_miniblock_num);
+                result.miniblock_hash = Some(last_miniblock_hash);
+            }
+            RegistryEvent::NodeAdded { node, .. }
+            | RegistryEvent::NodeRemoved { node }
+            | RegistryEvent::NodeStatusUpdated { node, .. }
+            | RegistryEvent::NodeUrlUpdated { node, .. } => {
+                result.nodes = Some(vec![node]);
+            }
+            RegistryEvent::Allocated { .. }
+            | RegistryEvent::Created { .. }
+            | RegistryEvent::Undecodable { .. } => {}
         }
 
-        let mid = (low + high) / 2;
+        result
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct StreamUpdates {
+    pub stream_id: StreamId,
+    pub updates: Vec<StreamUpdate>,
+    /// number of blocks that were read from the local index, only the logs for the stream are
+    /// read from it
+    pub indexed_blocks: u64,
+    /// number of logs that were downloaded from the chain to find the stream updates, `None` if
+    /// all blocks were read from the index
+    pub logs_scanned: Option<usize>,
+    /// number of logs with an update for the stream
+    pub logs_matched: usize,
+}
+
+impl Report for StreamUpdates {
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("stream", self.stream_id.to_string()),
+            ("indexed blocks", self.indexed_blocks.to_string()),
+            (
+                "logs scanned",
+                self.logs_scanned
+                    .map_or_else(|| "n/a, read from the index".to_string(), |scanned| scanned.to_string()),
+            ),
+            ("logs matched", self.logs_matched.to_string()),
+        ]
+    }
 
-        let stream = streams_registry
-            .getStream(stream_id.as_fixed_bytes32())
-            .block(BlockId::Number(BlockNumberOrTag::Number(mid)))
-            .call()
-            .await;
+    fn table(&self) -> Option<Table> {
+        Some(Table {
+            columns: [
+                "event",
+                "river_block",
+                "time",
+                "transaction",
+                "miniblock_num",
+                "miniblock_hash",
+                "nodes",
+                "replication_factor",
+            ]
+            .map(String::from)
+            .to_vec(),
+            rows: self
+                .updates
+                .iter()
+                .map(|update| {
+                    vec![
+                        update.event.to_string(),
+                        update.river_block.to_string(),
+                        update.time.as_ref().map(output::time).unwrap_or_default(),
+                        update.transaction.to_string(),
+                        output::opt(&update.miniblock_num),
+                        output::opt(&update.miniblock_hash),
+                        update.nodes.as_deref().map(output::list).unwrap_or_default(),
+                        output::opt(&update.replication_factor),
+                    ]
+                })
+                .collect(),
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct ActiveStreamsBucket {
+    /// first river block of the hour
+    pub river_block: u64,
+    pub time: DateTime<Utc>,
+    /// number of active streams for each hot duration
+    pub active_streams: Vec<usize>,
+}
 
-        if stream.is_err() {
-            low = mid + 1;
-        } else {
-            high = mid - 1;
+/// Stream ids with an unknown prefix that were seen while scanning registry events.
+#[derive(Default, Serialize)]
+pub(crate) struct UnrecognisedStreamIds {
+    pub stream_ids: BTreeSet<StreamId>,
+}
+
+impl UnrecognisedStreamIds {
+    /// Record the stream id when it has an unknown prefix, returns true if it was recorded.
+    pub(crate) fn record(&mut self, stream_id: &StreamId) -> bool {
+        if stream_id.is_unknown() {
+            if self.stream_ids.insert(*stream_id) {
+                eprintln!("unrecognised stream id: {}", stream_id);
+            }
+            return true;
         }
+        false
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.stream_ids.is_empty()
     }
 
-    let block_number = BlockNumberOrTag::Number(low);
-    let query = Filter::new()
-        .address(cfg.registry.address)
-        .from_block(block_number)
-        .to_block(block_number);
-
-    let logs = provider.get_logs(&query).await?;
-
-    let print_inception = |stream_id: StreamId,
-                           log: &Log,
-                           nodes: &[Address],
-                           genesis_hash: &FixedBytes<32>,
-                           genesis_block: &Option<Bytes>| {
-        println!("         stream: {}", stream_id);
-        println!("    river block: #{}", log.block_number.unwrap());
-        println!("     block hash: {}", log.block_hash.unwrap());
-        println!("    transaction: {}", log.transaction_hash.unwrap());
-        println!("inititial nodes: {:?}", nodes);
-        println!("   genesis hash: {}", genesis_hash);
-        if let Some(genesis_block) = genesis_block {
-            println!("genesis miniblock:");
-            println!("{}", genesis_block);
+    /// Summary with the number of unrecognised stream ids per prefix.
+    pub(crate) fn summary(&self) -> String {
+        let mut prefixes: BTreeMap<u8, usize> = BTreeMap::new();
+        for stream_id in &self.stream_ids {
+            *prefixes.entry(stream_id.stream_type()).or_default() += 1;
         }
-    };
 
-    let stream_id_fixed_bytes32 = stream_id.as_fixed_bytes32();
-
-    for log in &logs {
-        // unified event model with StreamAllocated encoded in StreamUpdated event.
-        if let Ok(stream_update) = log.log_decode::<StreamsRegistry::StreamUpdated>() {
-            let stream_update_event = stream_update.into_inner();
-            if stream_update_event.eventType == 0u8 {
-                let stream_state = StreamState::abi_decode_params(&stream_update_event.data.data)?;
-                let mut genesis_block = None;
-
-                if stream_state.streamId == stream_id_fixed_bytes32 {
-                    if let Ok(stream) = streams_registry
-                        .getStreamWithGenesis(stream_id.as_fixed_bytes32())
-                        .block(BlockId::Number(block_number))
-                        .call()
-                        .await
-                    {
-                        genesis_block = Some(stream._2);
-                    }
+        let per_prefix = prefixes
+            .iter()
+            .map(|(prefix, count)| format!("{:#04x}: {}", prefix, count))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("{} ({})", self.stream_ids.len(), per_prefix)
+    }
+}
 
-                    print_inception(
-                        stream_id,
-                        log,
-                        &stream_state.stream.nodes,
-                        &stream_state.stream.lastMiniblockHash,
-                        &genesis_block,
-                    );
+#[derive(Serialize)]
+pub(crate) struct ActiveStreams {
+    pub hot_duration_hours: Vec<u64>,
+    pub buckets: Vec<ActiveStreamsBucket>,
+    pub unrecognised: UnrecognisedStreamIds,
+}
 
-                    return Ok(());
-                }
-            }
+impl Report for ActiveStreams {
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        if self.unrecognised.is_empty() {
+            return vec![];
         }
+        vec![("unrecognised stream ids", self.unrecognised.summary())]
+    }
+
+    fn table(&self) -> Option<Table> {
+        let mut columns = vec!["river_block".to_string(), "time".to_string()];
+        columns.extend(
+            self.hot_duration_hours
+                .iter()
+                .map(|hot_duration_h| format!("hot_duration_{}_h", hot_duration_h)),
+        );
+
+        Some(Table {
+            columns,
+            rows: self
+                .buckets
+                .iter()
+                .map(|bucket| {
+                    [bucket.river_block.to_string(), output::time(&bucket.time)]
+                        .into_iter()
+                        .chain(bucket.active_streams.iter().map(ToString::to_string))
+                        .collect()
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Get stream inception event.
+pub(crate) async fn inception(cfg: &config::Config, stream_id: StreamId) -> eyre::Result<Inception> {
+    let client = cfg.registry_client()?;
+    let river_block = cfg.river_block(&client).await?;
+    let index = index::open(cfg)?;
+
+    let inception = find_inception(&client, index.as_ref(), &stream_id, river_block).await?;
+    let time = snapshot::block_time(&client, inception.river_block).await?;
+    Ok(Inception::new(inception, time))
+}
 
-        // old event model that emites StreamsRegistry::StreamAllocated
-        if matches!(
-            log.topic0(),
-            Some(&StreamsRegistry::StreamAllocated::SIGNATURE_HASH)
-        ) {
-            let stream_allocated_event =
-                towns_protocol_contracts::StreamAllocated::abi_decode_params(&log.data().data)?;
-            if stream_allocated_event.streamId == stream_id.as_fixed_bytes32() {
-                print_inception(
-                    stream_id,
-                    log,
-                    &stream_allocated_event.nodes,
-                    &stream_allocated_event.genesisMiniblockHash,
-                    &Some(stream_allocated_event.genesisMiniblock.clone()),
-                );
-
-                return Ok(());
+/// Find the inception of the stream at or before the river block. The local index is searched
+/// first, the remote search only covers the blocks the index doesn't.
+pub(crate) async fn find_inception<P: Provider>(
+    client: &RegistryClient<P>,
+    index: Option<&index::Index>,
+    stream_id: &StreamId,
+    river_block: u64,
+) -> eyre::Result<registry::Inception> {
+    let mut from = client.deployment_block();
+
+    if let Some(index) = index {
+        if let Some(covered) = index.covered(&(from..=river_block))? {
+            let inception = index
+                .stream_events(stream_id, covered.clone())?
+                .into_iter()
+                .find(|log| matches!(log.event, RegistryEvent::Allocated { .. } | RegistryEvent::Created { .. }));
+            if let Some(log) = inception {
+                return Ok(client.inception_from_log(stream_id, &log).await?);
             }
+            from = covered.end() + 1;
         }
     }
 
-    Err(TownsError::NotFound.into())
+    client
+        .inception(stream_id, from..=river_block)
+        .await
+        .wrap_err_with(|| format!("Failed to find inception of stream {}", stream_id))
 }
 
 /// Get stream details
-pub(crate) async fn details(cfg: &config::Config, stream_id: StreamId, river_block: Option<u64>) -> eyre::Result<()> {
-    let provider = cfg
-        .river_chain_provider()
-        .wrap_err("Invalid River chain RPC URL")?;
-    let streams_registry = StreamsRegistry::new(cfg.registry.address, &provider);
-    let block_number = river_block.unwrap_or(provider.get_block_number().await?);
-
-    let stream = streams_registry
-        .getStream(stream_id.as_fixed_bytes32())
-        .block(BlockId::Number(BlockNumberOrTag::Number(block_number)))
-        .call()
+pub(crate) async fn details(cfg: &config::Config, stream_id: StreamId, river_block: Option<u64>) -> eyre::Result<StreamDetails> {
+    let client = cfg.registry_client()?;
+    let block_number = match river_block {
+        Some(river_block) => river_block,
+        None => cfg.river_block(&client).await?,
+    };
+
+    let stream = client
+        .stream_at(&stream_id, BlockId::Number(BlockNumberOrTag::Number(block_number)))
         .await
         .wrap_err("Failed to get stream")?;
-
-    println!("     stream: {}", stream_id);
-    println!("  miniblock: {}", stream.lastMiniblockNum);
-    println!("       hash: {}", stream.lastMiniblockHash);
-    println!("      nodes: {:?}", stream.nodes);
-    println!("repl factor: {}", stream.replication_factor());
-    println!("river block: {}", block_number);
-
-    Ok(())
+    let time = snapshot::block_time(&client, block_number).await?;
+
+    Ok(StreamDetails {
+        stream_id,
+        miniblock_num: stream.lastMiniblockNum,
+        miniblock_hash: stream.lastMiniblockHash,
+        replication_factor: stream.replication_factor(),
+        sealed: stream.is_sealed(),
+        flags: stream.stream_flags(),
+        reserved0: stream.meta(),
+        nodes: stream.nodes,
+        river_block: block_number,
+        time,
+    })
 }
 
 /// Get total number of streams
-pub(crate) async fn count(cfg: &config::Config) -> eyre::Result<()> {
-    let provider = cfg
-        .river_chain_provider()
-        .wrap_err("Invalid River chain RPC URL")?;
-    let streams_registry = StreamsRegistry::new(cfg.registry.address, &provider);
-    let block_number = provider.get_block_number().await?;
-
-    let count = streams_registry
-        .getStreamCount()
-        .block(BlockId::Number(BlockNumberOrTag::Number(block_number)))
-        .call()
+pub(crate) async fn count(cfg: &config::Config) -> eyre::Result<StreamCount> {
+    let client = cfg.registry_client()?;
+    let block_number = cfg.river_block(&client).await?;
+
+    let count = client
+        .stream_count(BlockId::Number(BlockNumberOrTag::Number(block_number)))
         .await
         .wrap_err("Failed to get stream count")?;
 
-    println!("    streams: {}", count);
-    println!("river block: {}", block_number);
-
-    Ok(())
+    Ok(StreamCount {
+        streams: count.try_into()?,
+        river_block: block_number,
+    })
 }
 
-/// Get stream updates in the last n river blocks
+/// Get stream updates in the last n river blocks, or since the given time, newest first. Stops
+/// at the stream allocation.
 pub(crate) async fn updates(
     cfg: &config::Config,
     stream_id: StreamId,
     scroll_back_river_blocks: u64,
-) -> eyre::Result<()> {
-    let provider = cfg
-        .river_chain_provider()
-        .wrap_err("Invalid River chain RPC URL")?;
-    let mut to = provider.get_block_number().await?;
-    let block_range: u64 = 2_500;
-    let river_blocks = scroll_back_river_blocks;
-    let first_river_block_to_check = max(0, to - river_blocks);
+    since: Option<Since>,
+) -> eyre::Result<StreamUpdates> {
+    let client = cfg.registry_client()?;
+    let to = cfg.river_block(&client).await?;
+    let from = match since {
+        Some(since) => since.first_block(&client, to).await?,
+        None => to.saturating_sub(scroll_back_river_blocks),
+    };
 
-    loop {
-        let from = max(0, to - block_range);
-        let from = max(first_river_block_to_check, from);
+    let index = index::open(cfg)?;
+    let (mut history, mut indexed_blocks, mut logs_scanned) = (Vec::new(), 0, None);
+    let events = index::event_stream(index.as_ref(), &client, Selection::Stream(&stream_id), from..=to)?;
+    let mut events = std::pin::pin!(events);
+    while let Some(chunk) = events.try_next().await.wrap_err("failed to get stream history")? {
+        match chunk.scanned {
+            Some(scanned) => *logs_scanned.get_or_insert(0) += scanned,
+            None => indexed_blocks += chunk.range.end() - chunk.range.start() + 1,
+        }
+        history.extend(chunk.events);
+    }
+    let logs_matched = history.len();
+
+    let mut updates: Vec<StreamUpdate> = Vec::new();
+    for update in history.into_iter().rev() {
+        let created = matches!(
+            update.event,
+            RegistryEvent::Allocated { .. } | RegistryEvent::Created { .. }
+        );
+        updates.push(update.into());
+        if created {
+            break;
+        }
+    }
 
-        let filter = Filter::new()
-            .address(cfg.registry.address)
-            .from_block(from)
-            .to_block(to);
+    let blocks: BTreeSet<u64> = updates.iter().map(|update| update.river_block).collect();
+    let times = snapshot::block_times(&client, blocks).await?;
+    for update in updates.iter_mut() {
+        update.time = times.get(&update.river_block).copied();
+    }
 
-        let logs = provider
-            .get_logs(&filter)
-            .await
-            .wrap_err("failed to get logs")?;
+    Ok(StreamUpdates {
+        stream_id,
+        updates,
+        indexed_blocks,
+        logs_scanned,
+        logs_matched,
+    })
+}
 
-        let stream_id_as_fixed_bytes32 = stream_id.as_fixed_bytes32();
+pub(crate) async fn active_streams(
+    cfg: &config::Config, 
+    scroll_back_hours: u64, 
+    stream_types: &[StreamKind],
+    mut hot_duration_hours: Vec<u64>,
+) -> eyre::Result<ActiveStreams> {
+    if hot_duration_hours.is_empty() {
+        hot_duration_hours = vec![4];
+    }
 
-        for log in logs.iter().rev() {
-            if let Ok(stream_update) = log.log_decode::<StreamsRegistry::StreamUpdated>() {
-                let stream_update_event = stream_update.into_inner();
+    let client = cfg.registry_client()?;
+    let index = index::open(cfg)?;
 
-                let event_type = match StreamEventType::try_from(stream_update_event.eventType) {
-                    Ok(event_type) => event_type,
-                    Err(_) => continue,
+    let highest_hot_duration_h = hot_duration_hours.iter().cloned().fold(0, u64::max);
+    let hours = scroll_back_hours + highest_hot_duration_h;
+
+    // buckets cover whole hours of wall-clock time, the current hour is still in progress and
+    // left out. hour_starts[i] is the first block of hour i, oldest hour first.
+    let last = cfg.river_block(&client).await?;
+    let end = snapshot::block_time(&client, last)
+        .await?
+        .duration_trunc(TimeDelta::hours(1))?;
+    let mut hour_starts = Vec::with_capacity(hours as usize + 1);
+    for hour in (0..=hours).rev() {
+        let start = end - TimeDelta::hours(hour as i64);
+        hour_starts.push(Since::Time(start).first_block(&client, last).await?);
+    }
+    let first = hour_starts[0];
+    let current_hour = hour_starts[hours as usize];
+
+    let mut hour_buckets: Vec<HashSet<StreamId>> = vec![HashSet::new(); hours as usize];
+    let mut unrecognised = UnrecognisedStreamIds::default();
+
+    if current_hour > first {
+        let range = first..=current_hour - 1;
+        let mut progress = output::Progress::new("active streams", range.clone());
+        let events = index::event_stream(index.as_ref(), &client, Selection::All, range)?;
+        let mut events = std::pin::pin!(events);
+        while let Some(chunk) = events.try_next().await.wrap_err("failed to get logs")? {
+            progress.advance(&chunk.range, chunk.events.len());
+
+            for log in chunk.events.iter() {
+                let RegistryEvent::MiniblockBatch(updates) = &log.event else {
+                    continue;
                 };
+                let hour = hour_starts.partition_point(|start| *start <= log.block_number) - 1;
 
-                // println!("tx: {:?} got stream update event: {:?}", log.transaction_hash, event_type);
-
-                match event_type {
-                    StreamEventType::Allocate => {
-                        let stream_state =
-                            StreamState::abi_decode_params(&stream_update_event.data.data)?;
-
-                        if stream_state.streamId != stream_id_as_fixed_bytes32 {
-                            continue;
-                        }
-
-                        println!(
-                            "StreamAllocated river block #{} / tx: {}",
-                            log.block_number.unwrap(),
-                            log.transaction_hash.unwrap()
-                        );
-
-                        return Ok(());
-                    }
-                    StreamEventType::Create => {
-                        let stream_state =
-                            StreamState::abi_decode_params(&stream_update_event.data.data)?;
-
-                        if stream_state.streamId != stream_id_as_fixed_bytes32 {
-                            continue;
-                        }
-
-                        println!(
-                            "StreamCreated river block #{} / tx: {}",
-                            log.block_number.unwrap(),
-                            log.transaction_hash.unwrap()
-                        );
-
-                        return Ok(());
-                    }
-                    StreamEventType::PlacementUpdated => {
-                        let stream_state =
-                            StreamState::abi_decode_params(&stream_update_event.data.data)?;
-
-                        if stream_state.streamId != stream_id_as_fixed_bytes32 {
-                            continue;
-                        }
-
-                        println!(
-                            "PlacementUpdate nodes: {:?} / replication factor: {} / river block #{} / tx: {} ",
-                            stream_state.stream.nodes,
-                            stream_state.replication_factor(),
-                            log.block_number.unwrap(),
-                            log.transaction_hash.unwrap(),
-                        );
-
+                for update in updates {
+                    // keep scanning when the registry contains stream ids gandalf doesn't know about
+                    let stream_id = StreamId::from_registry(&update.stream_id);
+                    if unrecognised.record(&stream_id) {
                         continue;
                     }
-                    StreamEventType::LastMiniblockBatchUpdated => {
-                        let miniblock_updates =
-                            SetMiniblockArray::abi_decode_params(&stream_update_event.data.data)
-                                .map_err(|e| {
-                                    TownsError::InvalidStreamUpdatedEvent(
-                                        e.to_string(),
-                                        log.transaction_hash.unwrap(),
-                                        log.log_index.unwrap(),
-                                    )
-                                })?;
-
-                        miniblock_updates.iter().for_each(|mb| {
-                            let update_stream_id = StreamId::from(&mb.streamId);
-                            if update_stream_id != stream_id {
-                                return;
-                            }
-
-                            println!("MiniblockUpdated miniblock_num: {} miniblock_hash: {} / river block #{} / tx: {} ",
-                                mb.lastMiniblockNum,
-                                mb.lastMiniblockHash.to_string(),
-                                log.block_number.unwrap(),
-                                log.transaction_hash.unwrap(),
-                            );
-                        });
-
-                        continue;
-                    }
-                    _ => {
-                        return Err(TownsError::InvalidStreamUpdatedEvent(
-                            "Invalid stream update event type".to_string(),
-                            log.transaction_hash.unwrap(),
-                            log.log_index.unwrap(),
-                        )
-                        .into());
+                    if stream_types.is_empty() || stream_types.contains(&stream_id.kind()) {
+                        hour_buckets[hour].insert(stream_id);
                     }
                 }
             }
+        }
+    }
 
-            let update = StreamUpdated::decode_log_data(log.data());
-            if update.is_err() {
-                continue;
-            }
+    let mut buckets = Vec::new();
 
-            let update = update.unwrap();
-            let event_type = match StreamEventType::try_from(update.eventType) {
-                Ok(event_type) => event_type,
-                Err(_) => continue,
-            };
-
-            match event_type {
-                StreamEventType::Allocate => {
-                    let stream_state =
-                        StreamState::abi_decode_params(&update.data).map_err(|e| {
-                            TownsError::InvalidStreamUpdatedEvent(
-                                e.to_string(),
-                                log.transaction_hash.unwrap(),
-                                log.log_index.unwrap(),
-                            )
-                        })?;
-
-                    if stream_state.streamId != stream_id.as_fixed_bytes32() {
-                        continue;
-                    }
+    for hour in (highest_hot_duration_h as usize..hours as usize).rev() {
+        let mut active_streams = Vec::with_capacity(hot_duration_hours.len());
+        for hot_duration_h in hot_duration_hours.iter() {
+            // get the unique streams that have seen activity in the last hot_duration buckets (hot_duration hour).
+            let mut unique_streams = hour_buckets[hour].clone();
 
-                    println!(
-                        "StreamAllocated river block #{} / tx: {}",
-                        log.block_number.unwrap(),
-                        log.transaction_hash.unwrap()
-                    );
+            for i in 1..*hot_duration_h as usize {
+                unique_streams.extend(hour_buckets[hour - i].iter().cloned());
+            }
+            active_streams.push(unique_streams.len());
+        }
+        buckets.push(ActiveStreamsBucket {
+            river_block: hour_starts[hour],
+            time: end - TimeDelta::hours((hours as usize - hour) as i64),
+            active_streams,
+        });
+    }
 
-                    return Ok(());
-                }
-                StreamEventType::Create => {
-                    let stream_state =
-                        StreamState::abi_decode_params(&update.data).map_err(|e| {
-                            TownsError::InvalidStreamUpdatedEvent(
-                                e.to_string(),
-                                log.transaction_hash.unwrap(),
-                                log.log_index.unwrap(),
-                            )
-                        })?;
-
-                    if stream_state.streamId != stream_id.as_fixed_bytes32() {
-                        continue;
-                    }
+    Ok(ActiveStreams {
+        hot_duration_hours,
+        buckets,
+        unrecognised,
+    })
+}
 
-                    println!(
-                        "StreamCreated river block #{} / tx: {}",
-                        log.block_number.unwrap(),
-                        log.transaction_hash.unwrap()
-                    );
+/// Stream record as listed from the registry.
+#[derive(Serialize)]
+pub(crate) struct ListedStream {
+    pub stream_id: StreamId,
+    #[serde(serialize_with = "output::display")]
+    pub stream_type: StreamKind,
+    pub miniblock_num: u64,
+    pub miniblock_hash: FixedBytes<32>,
+    pub replication_factor: u64,
+    pub sealed: bool,
+    pub flags: u64,
+    pub reserved0: u64,
+    pub nodes: Vec<Address>,
+}
 
-                    return Ok(());
-                }
-                StreamEventType::PlacementUpdated => {
-                    let stream_state =
-                        StreamState::abi_decode_params(&update.data).map_err(|e| {
-                            TownsError::InvalidStreamUpdatedEvent(
-                                e.to_string(),
-                                log.transaction_hash.unwrap(),
-                                log.log_index.unwrap(),
-                            )
-                        })?;
-
-                    if stream_state.streamId != stream_id.as_fixed_bytes32() {
-                        continue;
-                    }
+#[derive(Serialize)]
+pub(crate) struct StreamList {
+    pub river_block: u64,
+    pub time: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node: Option<Address>,
+    /// number of stream records read from the registry
+    pub scanned: u64,
+    pub streams: Vec<ListedStream>,
+    pub unrecognised: UnrecognisedStreamIds,
+}
 
-                    println!(
-                        "PlacementUpdate nodes: {:?} / replication factor: {} / river block #{} / tx: {} ",
-                        stream_state.stream.nodes,
-                        stream_state.replication_factor(),
-                        log.block_number.unwrap(),
-                        log.transaction_hash.unwrap(),
-                    );
-                }
-                StreamEventType::LastMiniblockBatchUpdated => {
-                    let miniblock_updates =
-                        SetMiniblockArray::abi_decode_params(&update.data).map_err(|e| {
-                            TownsError::InvalidStreamUpdatedEvent(
-                                e.to_string(),
-                                log.transaction_hash.unwrap(),
-                                log.log_index.unwrap(),
-                            )
-                        })?;
-
-                    miniblock_updates.iter().for_each(|mb| {
-                        let update_stream_id = StreamId::from(&mb.streamId);
-                        if update_stream_id != stream_id {
-                            return;
-                        }
-
-                        println!("MiniblockUpdated miniblock_num: {} miniblock_hash: {} / river block #{} / tx: {} ",
-                            mb.lastMiniblockNum,
-                            mb.lastMiniblockHash.to_string(),
-                            log.block_number.unwrap(),
-                            log.transaction_hash.unwrap(),
-                        );
-                    });
-                }
-                _ => {
-                    return Err(TownsError::InvalidStreamUpdatedEvent(
-                        "invalid stream update event type".to_string(),
-                        log.transaction_hash.unwrap(),
-                        log.log_index.unwrap(),
-                    )
-                    .into());
-                }
-            }
+impl Report for StreamList {
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![
+            ("river block", self.river_block.to_string()),
+            ("time", output::time(&self.time)),
+        ];
+        if let Some(node) = &self.node {
+            fields.push(("node", node.to_string()));
         }
-
-        if from == 0 || from == first_river_block_to_check {
-            break;
+        fields.push(("scanned", self.scanned.to_string()));
+        fields.push(("matched", self.streams.len().to_string()));
+        if !self.unrecognised.is_empty() {
+            fields.push(("unrecognised stream ids", self.unrecognised.summary()));
         }
+        fields
+    }
 
-        to = to - block_range - 1;
+    fn table(&self) -> Option<Table> {
+        Some(Table {
+            columns: ["stream_id", "stream_type", "miniblock_num", "repl_factor", "sealed", "flags", "nodes"]
+                .map(String::from)
+                .to_vec(),
+            rows: self
+                .streams
+                .iter()
+                .map(|stream| {
+                    vec![
+                        stream.stream_id.to_string(),
+                        stream.stream_type.to_string(),
+                        stream.miniblock_num.to_string(),
+                        stream.replication_factor.to_string(),
+                        stream.sealed.to_string(),
+                        format!("{:#x}", stream.flags),
+                        output::list(&stream.nodes),
+                    ]
+                })
+                .collect(),
+        })
     }
+}
 
-    Ok(())
+/// Selects streams from the registry by type, sealed state and replication factor. Unset
+/// criteria match all streams.
+#[derive(Debug, Default)]
+pub(crate) struct StreamFilter {
+    pub stream_types: Vec<StreamKind>,
+    pub sealed: Option<bool>,
+    pub replication_factor: Option<u64>,
 }
 
-pub(crate) async fn active_streams(
-    cfg: &config::Config, 
-    scroll_back_hours: u64, 
-    stream_types: &Vec<u8>, 
-    mut hot_duration_hours: Vec<u64>,
-) -> eyre::Result<()> {
-    if hot_duration_hours.is_empty() {
-        hot_duration_hours = vec![4];
+impl StreamFilter {
+    pub(crate) fn matches(&self, stream_id: &StreamId, stream: &StreamsRegistry::Stream) -> bool {
+        (self.stream_types.is_empty() || self.stream_types.contains(&stream_id.kind()))
+            && self.sealed.is_none_or(|sealed| stream.is_sealed() == sealed)
+            && self.replication_factor.is_none_or(|repl_factor| stream.replication_factor() == repl_factor)
     }
+}
 
-    let provider = cfg
-        .river_chain_provider()
-        .wrap_err("Invalid River chain RPC URL")?;
-    
-    let highest_hot_duration_h = hot_duration_hours.iter().cloned().fold(0, u64::max);
-    let block_range_1h = 1800;
-    let last = (provider.get_block_number().await? / block_range_1h) * block_range_1h;
-    let history = block_range_1h * (scroll_back_hours + highest_hot_duration_h + 1);
-    let first = if history < last { last-history } else { 0 };
-    
-    let mut river_block_buckets: BTreeMap<u64, HashSet<StreamId>> = BTreeMap::new();
-    
-    for from in (first..last).step_by(block_range_1h as usize) {
-        let to = from+block_range_1h -1;
-
-        let filter = Filter::new()
-            .address(cfg.registry.address)
-            .from_block(from)
-            .to_block(to);
-
-        let logs = provider
-            .get_logs(&filter)
+/// List the streams in the registry, or the streams placed on a node, that match the filter.
+pub(crate) async fn list(
+    cfg: &config::Config,
+    filter: &StreamFilter,
+    node: Option<Address>,
+    page_size: u64,
+    limit: Option<usize>,
+) -> eyre::Result<StreamList> {
+    let client = cfg.registry_client()?;
+    let river_block = cfg.river_block(&client).await?;
+    let block = BlockId::Number(BlockNumberOrTag::Number(river_block));
+    let total = match node {
+        Some(node) => client.stream_count_on_node(node, block).await,
+        None => client.stream_count(block).await,
+    }
+    .wrap_err("Failed to get stream count")?;
+    let total: u64 = total.try_into()?;
+
+    let mut streams = Vec::new();
+    let mut unrecognised = UnrecognisedStreamIds::default();
+    let mut scanned = 0;
+    let mut progress =
+        output::Progress::new("streams", 0..=total.saturating_sub(1)).with_units("stream", "matched");
+    loop {
+        let (page, last) = client
+            .streams_page(node, scanned, page_size, block)
             .await
-            .wrap_err("failed to get logs")?;
-
-        eprintln!("from: {} / to: {} / logs: {}", from, to, logs.len());
+            .wrap_err("Failed to get streams")?;
+        let (page_start, matched) = (scanned, streams.len());
+        scanned += page.len() as u64;
 
-        for log in logs.iter() {
-            if let Ok(stream_update) = log.log_decode::<StreamsRegistry::StreamUpdated>() {
-                let stream_update_event = stream_update.into_inner();
-                let event_type = match StreamEventType::try_from(stream_update_event.eventType) {
-                    Ok(event_type) => event_type,
-                    Err(_) => continue,
-                };
-
-                if event_type == StreamEventType::LastMiniblockBatchUpdated {
-                    let miniblock_updates = SetMiniblockArray::abi_decode_params(&stream_update_event.data.data)
-                        .wrap_err("failed to decode miniblock updates")?;
-
-                    miniblock_updates.iter().for_each(|mb| {
-                        let stream_id: StreamId = match StreamId::try_from(&mb.streamId)   {
-                            Ok(stream_id) => stream_id,
-                            Err(err) => panic!("invalid stream id: {}", err),
-                        };
-
-                        if stream_types.is_empty() || stream_types.contains(&stream_id.stream_type()) {
-                            let bucket_key = block_range_1h * (log.block_number.unwrap() / block_range_1h);
-                            if let Some(streams) = river_block_buckets.get_mut(&bucket_key) {
-                                streams.insert(stream_id);
-                            } else {
-                                river_block_buckets.insert(bucket_key, HashSet::from([stream_id]));
-                            }
-                        }
-                    });
-                }
+        for entry in page {
+            let stream_id = StreamId::from_registry(&entry.id);
+            if unrecognised.record(&stream_id) || !filter.matches(&stream_id, &entry.stream) {
+                continue;
             }
+            streams.push(ListedStream {
+                stream_id,
+                stream_type: stream_id.kind(),
+                miniblock_num: entry.stream.lastMiniblockNum,
+                miniblock_hash: entry.stream.lastMiniblockHash,
+                replication_factor: entry.stream.replication_factor(),
+                sealed: entry.stream.is_sealed(),
+                flags: entry.stream.flags,
+                reserved0: entry.stream.reserved0,
+                nodes: entry.stream.nodes,
+            });
         }
-    }
-
-    print!("river_block");
-    for hot_duration_h in hot_duration_hours.iter() {
-        print!(",hot_duration_{}_h", hot_duration_h);
-    }
-    println!();
 
-    for (block, streams) in river_block_buckets.iter().rev().take(scroll_back_hours as usize) {
-        print!("{block}",);
-        for hot_duration_h in hot_duration_hours.iter() {
-            // get the unique streams that have seen activity in the last hot_duration buckets (hot_duration hour).
-            let mut unique_streams = streams.clone();
+        if scanned > page_start {
+            progress.advance(&(page_start..=scanned - 1), streams.len() - matched);
+        }
 
-            for i in 1..*hot_duration_h {
-                let bucket_key = block - (i*block_range_1h);
-                if let Some((_, bucket)) = river_block_buckets.get_key_value(&bucket_key) {
-                    unique_streams = unique_streams.union(bucket).cloned().collect();
-                }
+        if let Some(limit) = limit {
+            if streams.len() >= limit {
+                streams.truncate(limit);
+                break;
             }
-            print!(",{}", unique_streams.len());
         }
-        println!();
+        if last {
+            break;
+        }
     }
 
-    Ok(())
+    Ok(StreamList {
+        river_block,
+        time: snapshot::block_time(&client, river_block).await?,
+        node,
+        scanned,
+        streams,
+        unrecognised,
+    })
 }
@@ -0,0 +1,567 @@
+use crate::index::Selection;
+use crate::output::{self, Report, Table};
+use crate::{config, index, snapshot, stream};
+use alloy_primitives::{Address, FixedBytes};
+use chrono::{DateTime, Utc};
+use eyre::WrapErr;
+use futures::TryStreamExt;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use towns_protocol_registry::{RegistryEvent, RegistryLog};
+use towns_protocol_types::StreamId;
+
+/// Change in the lifecycle of a stream.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum TimelineEvent {
+    Allocated {
+        nodes: Vec<Address>,
+        replication_factor: u64,
+        genesis_hash: FixedBytes<32>,
+    },
+    Created {
+        nodes: Vec<Address>,
+        replication_factor: u64,
+        genesis_hash: FixedBytes<32>,
+    },
+    NodeAdded {
+        node: Address,
+    },
+    NodeRemoved {
+        node: Address,
+    },
+    ReplicationFactor {
+        from: u64,
+        to: u64,
+    },
+    /// consecutive miniblocks registered from river_block up to and including last_river_block
+    Miniblocks {
+        first: u64,
+        last: u64,
+        registrations: usize,
+        last_river_block: u64,
+    },
+    /// miniblocks that were skipped by the next registration
+    MiniblockGap {
+        first: u64,
+        last: u64,
+    },
+    UpdateFailed {
+        miniblock_num: u64,
+        miniblock_hash: FixedBytes<32>,
+        reason: String,
+    },
+    Sealed {
+        miniblock_num: u64,
+    },
+}
+
+impl TimelineEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            TimelineEvent::Allocated { .. } => "allocated",
+            TimelineEvent::Created { .. } => "created",
+            TimelineEvent::NodeAdded { .. } => "node added",
+            TimelineEvent::NodeRemoved { .. } => "node removed",
+            TimelineEvent::ReplicationFactor { .. } => "replication factor",
+            TimelineEvent::Miniblocks { .. } => "miniblocks",
+            TimelineEvent::MiniblockGap { .. } => "miniblock gap",
+            TimelineEvent::UpdateFailed { .. } => "update failed",
+            TimelineEvent::Sealed { .. } => "sealed",
+        }
+    }
+
+    fn details(&self) -> String {
+        match self {
+            TimelineEvent::Allocated {
+                nodes,
+                replication_factor,
+                genesis_hash,
+            }
+            | TimelineEvent::Created {
+                nodes,
+                replication_factor,
+                genesis_hash,
+            } => format!(
+                "nodes {}, repl factor {}, genesis {}",
+                output::list(nodes),
+                replication_factor,
+                genesis_hash
+            ),
+            TimelineEvent::NodeAdded { node } | TimelineEvent::NodeRemoved { node } => {
+                node.to_string()
+            }
+            TimelineEvent::ReplicationFactor { from, to } => format!("{} -> {}", from, to),
+            TimelineEvent::Miniblocks {
+                first,
+                last,
+                registrations,
+                last_river_block,
+            } => format!(
+                "{}..={}, {} registrations, last in river block {}",
+                first, last, registrations, last_river_block
+            ),
+            TimelineEvent::MiniblockGap { first, last } => {
+                format!("{}..={} not registered", first, last)
+            }
+            TimelineEvent::UpdateFailed {
+                miniblock_num,
+                miniblock_hash,
+                reason,
+            } => format!("miniblock {} {}: {}", miniblock_num, miniblock_hash, reason),
+            TimelineEvent::Sealed { miniblock_num } => format!("miniblock {}", miniblock_num),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct TimelineEntry {
+    pub river_block: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<DateTime<Utc>>,
+    pub transaction: FixedBytes<32>,
+    #[serde(flatten)]
+    pub event: TimelineEvent,
+}
+
+/// Lifecycle of a stream from its inception up to the river block, oldest first.
+#[derive(Serialize)]
+pub(crate) struct StreamTimeline {
+    pub stream_id: StreamId,
+    pub river_block: u64,
+    pub nodes: Vec<Address>,
+    pub replication_factor: Option<u64>,
+    pub last_miniblock_num: Option<u64>,
+    pub sealed: bool,
+    pub failed_updates: usize,
+    pub entries: Vec<TimelineEntry>,
+}
+
+impl Report for StreamTimeline {
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("stream", self.stream_id.to_string()),
+            ("river block", self.river_block.to_string()),
+            ("nodes", output::list(&self.nodes)),
+            ("repl factor", output::opt(&self.replication_factor)),
+            ("last miniblock", output::opt(&self.last_miniblock_num)),
+            ("sealed", self.sealed.to_string()),
+            ("failed updates", self.failed_updates.to_string()),
+        ]
+    }
+
+    fn table(&self) -> Option<Table> {
+        Some(Table {
+            columns: ["river_block", "time", "event", "details"]
+                .map(String::from)
+                .to_vec(),
+            rows: self
+                .entries
+                .iter()
+                .map(|entry| {
+                    vec![
+                        entry.river_block.to_string(),
+                        entry.time.as_ref().map(output::time).unwrap_or_default(),
+                        entry.event.name().to_string(),
+                        entry.event.details(),
+                    ]
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Builds the timeline from the stream events in the order they were emitted. Consecutive
+/// miniblock registrations are merged into a single entry.
+#[derive(Debug, Default)]
+struct TimelineBuilder {
+    entries: Vec<TimelineEntry>,
+    nodes: Vec<Address>,
+    replication_factor: Option<u64>,
+    last_miniblock_num: Option<u64>,
+    sealed: bool,
+    failed_updates: usize,
+}
+
+impl TimelineBuilder {
+    fn push(&mut self, log: &RegistryLog, event: TimelineEvent) {
+        self.entries.push(TimelineEntry {
+            river_block: log.block_number,
+            time: None,
+            transaction: log.transaction_hash,
+            event,
+        });
+    }
+
+    fn apply(&mut self, log: &RegistryLog) {
+        match &log.event {
+            RegistryEvent::Allocated {
+                nodes,
+                replication_factor,
+                genesis_hash,
+                ..
+            } => {
+                self.nodes = nodes.clone();
+                self.replication_factor = Some(*replication_factor);
+                self.last_miniblock_num = Some(0);
+                self.push(
+                    log,
+                    TimelineEvent::Allocated {
+                        nodes: nodes.clone(),
+                        replication_factor: *replication_factor,
+                        genesis_hash: *genesis_hash,
+                    },
+                );
+            }
+            RegistryEvent::Created {
+                nodes,
+                replication_factor,
+                genesis_hash,
+                ..
+            } => {
+                self.nodes = nodes.clone();
+                self.replication_factor = Some(*replication_factor);
+                self.last_miniblock_num = Some(0);
+                self.push(
+                    log,
+                    TimelineEvent::Created {
+                        nodes: nodes.clone(),
+                        replication_factor: *replication_factor,
+                        genesis_hash: *genesis_hash,
+                    },
+                );
+            }
+            RegistryEvent::PlacementUpdated {
+                nodes,
+                replication_factor,
+                ..
+            } => {
+                let previous = std::mem::replace(&mut self.nodes, nodes.clone());
+                for node in nodes.iter().filter(|node| !previous.contains(node)) {
+                    self.push(log, TimelineEvent::NodeAdded { node: *node });
+                }
+                for node in previous.iter().filter(|node| !nodes.contains(node)) {
+                    self.push(log, TimelineEvent::NodeRemoved { node: *node });
+                }
+                if let Some(from) = self
+                    .replication_factor
+                    .filter(|from| from != replication_factor)
+                {
+                    self.push(
+                        log,
+                        TimelineEvent::ReplicationFactor {
+                            from,
+                            to: *replication_factor,
+                        },
+                    );
+                }
+                self.replication_factor = Some(*replication_factor);
+            }
+            RegistryEvent::NodePlacementUpdated { node, is_added, .. } => {
+                if *is_added {
+                    self.nodes.push(*node);
+                    self.push(log, TimelineEvent::NodeAdded { node: *node });
+                } else {
+                    self.nodes.retain(|n| n != node);
+                    self.push(log, TimelineEvent::NodeRemoved { node: *node });
+                }
+            }
+            RegistryEvent::MiniblockBatch(updates) => {
+                for update in updates {
+                    self.miniblock(log, update.last_miniblock_num);
+                    if update.is_sealed && !self.sealed {
+                        self.sealed = true;
+                        self.push(
+                            log,
+                            TimelineEvent::Sealed {
+                                miniblock_num: update.last_miniblock_num,
+                            },
+                        );
+                    }
+                }
+            }
+            RegistryEvent::MiniblockUpdateFailed {
+                last_miniblock_hash,
+                last_miniblock_num,
+                reason,
+                ..
+            } => {
+                self.failed_updates += 1;
+                self.push(
+                    log,
+                    TimelineEvent::UpdateFailed {
+                        miniblock_num: *last_miniblock_num,
+                        miniblock_hash: *last_miniblock_hash,
+                        reason: reason.clone(),
+                    },
+                );
+            }
+            RegistryEvent::NodeAdded { .. }
+            | RegistryEvent::NodeRemoved { .. }
+            | RegistryEvent::NodeStatusUpdated { .. }
+            | RegistryEvent::NodeUrlUpdated { .. }
+            | RegistryEvent::Undecodable { .. } => {}
+        }
+    }
+
+    /// Record a miniblock registration, extending the last entry when it directly follows it.
+    fn miniblock(&mut self, log: &RegistryLog, num: u64) {
+        let previous = self.last_miniblock_num.replace(num);
+
+        if let Some(previous) = previous.filter(|previous| num > previous + 1) {
+            self.push(
+                log,
+                TimelineEvent::MiniblockGap {
+                    first: previous + 1,
+                    last: num - 1,
+                },
+            );
+        }
+
+        if let Some(TimelineEntry {
+            event:
+                TimelineEvent::Miniblocks {
+                    last,
+                    registrations,
+                    last_river_block,
+                    ..
+                },
+            ..
+        }) = self.entries.last_mut()
+        {
+            if num == *last + 1 {
+                *last = num;
+                *registrations += 1;
+                *last_river_block = log.block_number;
+                return;
+            }
+        }
+
+        self.push(
+            log,
+            TimelineEvent::Miniblocks {
+                first: num,
+                last: num,
+                registrations: 1,
+                last_river_block: log.block_number,
+            },
+        );
+    }
+}
+
+/// Build the lifecycle of the stream from its inception up to the river block.
+pub(crate) async fn timeline(
+    cfg: &config::Config,
+    stream_id: StreamId,
+) -> eyre::Result<StreamTimeline> {
+    let client = cfg.registry_client()?;
+    let river_block = cfg.river_block(&client).await?;
+    let index = index::open(cfg)?;
+
+    let inception =
+        stream::find_inception(&client, index.as_ref(), &stream_id, river_block).await?;
+    let range = inception.river_block..=river_block;
+
+    let mut builder = TimelineBuilder::default();
+    let mut progress = output::Progress::new("timeline", range.clone());
+    let events = index::event_stream(
+        index.as_ref(),
+        &client,
+        Selection::Stream(&stream_id),
+        range,
+    )?;
+    let mut events = std::pin::pin!(events);
+    while let Some(chunk) = events
+        .try_next()
+        .await
+        .wrap_err("failed to get stream history")?
+    {
+        progress.advance(&chunk.range, chunk.events.len());
+        for log in &chunk.events {
+            builder.apply(log);
+        }
+    }
+
+    let blocks: BTreeSet<u64> = builder
+        .entries
+        .iter()
+        .map(|entry| entry.river_block)
+        .collect();
+    let times = snapshot::block_times(&client, blocks).await?;
+    for entry in builder.entries.iter_mut() {
+        entry.time = times.get(&entry.river_block).copied();
+    }
+
+    Ok(StreamTimeline {
+        stream_id,
+        river_block,
+        nodes: builder.nodes,
+        replication_factor: builder.replication_factor,
+        last_miniblock_num: builder.last_miniblock_num,
+        sealed: builder.sealed,
+        failed_updates: builder.failed_updates,
+        entries: builder.entries,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use towns_protocol_registry::MiniblockUpdate;
+
+    fn log(block_number: u64, event: RegistryEvent) -> RegistryLog {
+        RegistryLog {
+            block_number,
+            block_hash: FixedBytes::ZERO,
+            transaction_hash: FixedBytes::repeat_byte(block_number as u8),
+            log_index: 0,
+            event,
+        }
+    }
+
+    fn miniblock(block_number: u64, num: u64, is_sealed: bool) -> RegistryLog {
+        log(
+            block_number,
+            RegistryEvent::MiniblockBatch(vec![MiniblockUpdate {
+                stream_id: FixedBytes::ZERO,
+                last_miniblock_hash: FixedBytes::repeat_byte(num as u8),
+                last_miniblock_num: num,
+                is_sealed,
+            }]),
+        )
+    }
+
+    fn events(builder: &TimelineBuilder) -> Vec<(u64, TimelineEvent)> {
+        builder
+            .entries
+            .iter()
+            .map(|entry| (entry.river_block, entry.event.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn build_stream_lifecycle() {
+        let (a, b, c) = (
+            Address::repeat_byte(1),
+            Address::repeat_byte(2),
+            Address::repeat_byte(3),
+        );
+        let logs = vec![
+            log(
+                10,
+                RegistryEvent::Allocated {
+                    stream_id: FixedBytes::ZERO,
+                    nodes: vec![a],
+                    replication_factor: 1,
+                    genesis_hash: FixedBytes::ZERO,
+                    genesis_miniblock: None,
+                },
+            ),
+            miniblock(11, 1, false),
+            miniblock(12, 2, false),
+            miniblock(13, 5, false),
+            log(
+                14,
+                RegistryEvent::MiniblockUpdateFailed {
+                    stream_id: FixedBytes::ZERO,
+                    last_miniblock_hash: FixedBytes::ZERO,
+                    last_miniblock_num: 7,
+                    reason: "BAD_PREV_MINIBLOCK_HASH".to_string(),
+                },
+            ),
+            log(
+                15,
+                RegistryEvent::PlacementUpdated {
+                    stream_id: FixedBytes::ZERO,
+                    nodes: vec![b, c],
+                    replication_factor: 2,
+                },
+            ),
+            miniblock(16, 6, true),
+        ];
+
+        let mut builder = TimelineBuilder::default();
+        for log in &logs {
+            builder.apply(log);
+        }
+
+        let miniblocks = |first, last, registrations, last_river_block| TimelineEvent::Miniblocks {
+            first,
+            last,
+            registrations,
+            last_river_block,
+        };
+        assert_eq!(
+            vec![
+                (
+                    10,
+                    TimelineEvent::Allocated {
+                        nodes: vec![a],
+                        replication_factor: 1,
+                        genesis_hash: FixedBytes::ZERO,
+                    }
+                ),
+                (11, miniblocks(1, 2, 2, 12)),
+                (13, TimelineEvent::MiniblockGap { first: 3, last: 4 }),
+                (13, miniblocks(5, 5, 1, 13)),
+                (
+                    14,
+                    TimelineEvent::UpdateFailed {
+                        miniblock_num: 7,
+                        miniblock_hash: FixedBytes::ZERO,
+                        reason: "BAD_PREV_MINIBLOCK_HASH".to_string(),
+                    }
+                ),
+                (15, TimelineEvent::NodeAdded { node: b }),
+                (15, TimelineEvent::NodeAdded { node: c }),
+                (15, TimelineEvent::NodeRemoved { node: a }),
+                (15, TimelineEvent::ReplicationFactor { from: 1, to: 2 }),
+                (16, miniblocks(6, 6, 1, 16)),
+                (16, TimelineEvent::Sealed { miniblock_num: 6 }),
+            ],
+            events(&builder)
+        );
+        assert_eq!(vec![b, c], builder.nodes);
+        assert_eq!(Some(2), builder.replication_factor);
+        assert_eq!(Some(6), builder.last_miniblock_num);
+        assert!(builder.sealed);
+        assert_eq!(1, builder.failed_updates);
+    }
+
+    #[test]
+    fn gap_after_created_stream() {
+        let logs = vec![
+            log(
+                10,
+                RegistryEvent::Created {
+                    stream_id: FixedBytes::ZERO,
+                    nodes: vec![Address::repeat_byte(1)],
+                    replication_factor: 1,
+                    genesis_hash: FixedBytes::ZERO,
+                },
+            ),
+            miniblock(11, 3, false),
+        ];
+
+        let mut builder = TimelineBuilder::default();
+        for log in &logs {
+            builder.apply(log);
+        }
+
+        assert_eq!(
+            vec![
+                (11, TimelineEvent::MiniblockGap { first: 1, last: 2 }),
+                (
+                    11,
+                    TimelineEvent::Miniblocks {
+                        first: 3,
+                        last: 3,
+                        registrations: 1,
+                        last_river_block: 11,
+                    }
+                ),
+            ],
+            events(&builder)[1..]
+        );
+    }
+}
@@ -0,0 +1,589 @@
+use crate::config;
+use crate::index::{self, Index, Selection};
+use crate::output::{self, OutputFormat, RowWriter};
+use crate::stream::StreamUpdate;
+use alloy_primitives::{Address, FixedBytes};
+use alloy_provider::Provider;
+use alloy_pubsub::Subscription;
+use alloy_rpc_types::{BlockId, BlockNumberOrTag, Header};
+use eyre::WrapErr;
+use futures::TryStreamExt;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::ops::RangeInclusive;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use towns_protocol_registry::{RegistryClient, RegistryEvent, RegistryLog};
+use towns_protocol_types::{StreamId, StreamKind};
+
+/// Selects the registry events that are printed. Empty filters match everything.
+#[derive(Debug, Default)]
+pub(crate) struct EventFilter {
+    pub stream_ids: Vec<StreamId>,
+    pub stream_types: Vec<StreamKind>,
+    pub node: Option<Address>,
+}
+
+impl EventFilter {
+    /// Returns the log restricted to the streams that match the filter, or `None` if nothing
+    /// in the log matches. Node events only match when no stream filter is set. The node filter
+    /// is applied by [`NodeStreams`].
+    fn apply(&self, log: &RegistryLog) -> Option<RegistryLog> {
+        if self.stream_ids.is_empty() && self.stream_types.is_empty() {
+            return Some(log.clone());
+        }
+
+        log.filter_streams(|stream_id| {
+            (self.stream_ids.is_empty()
+                || self
+                    .stream_ids
+                    .iter()
+                    .any(|id| id.as_fixed_bytes32() == *stream_id))
+                && (self.stream_types.is_empty()
+                    || StreamKind::from_prefix(stream_id[0])
+                        .is_some_and(|kind| self.stream_types.contains(&kind)))
+        })
+    }
+}
+
+/// Number of streams requested per call when loading the streams placed on a node.
+const NODE_STREAMS_PAGE_SIZE: u64 = 1000;
+
+/// Streams placed on the node of the `--node` filter. Miniblock updates and failures don't carry
+/// the placement, so the streams are loaded once and kept up to date with the placement events.
+#[derive(Debug)]
+struct NodeStreams {
+    node: Address,
+    streams: HashSet<FixedBytes<32>>,
+}
+
+impl NodeStreams {
+    /// Load the streams placed on the node at the given river block.
+    async fn load<P: Provider>(
+        client: &RegistryClient<P>,
+        node: Address,
+        block: u64,
+    ) -> eyre::Result<Self> {
+        let block_id = BlockId::Number(BlockNumberOrTag::Number(block));
+        let mut streams = HashSet::new();
+        loop {
+            let (page, last) = client
+                .streams_page(
+                    Some(node),
+                    streams.len() as u64,
+                    NODE_STREAMS_PAGE_SIZE,
+                    block_id,
+                )
+                .await
+                .wrap_err("Failed to get streams placed on node")?;
+            streams.extend(page.into_iter().map(|entry| entry.id));
+            if last {
+                break;
+            }
+        }
+        eprintln!(
+            "node {} has {} streams placed in block {}",
+            node,
+            streams.len(),
+            block
+        );
+        Ok(NodeStreams { node, streams })
+    }
+
+    /// Update the placement with the log and return the log restricted to the node: events about
+    /// the node, placements that add or remove it and the miniblock updates and failures of the
+    /// streams placed on it.
+    fn apply(&mut self, log: &RegistryLog) -> Option<RegistryLog> {
+        match &log.event {
+            RegistryEvent::Allocated {
+                stream_id, nodes, ..
+            }
+            | RegistryEvent::Created {
+                stream_id, nodes, ..
+            }
+            | RegistryEvent::PlacementUpdated {
+                stream_id, nodes, ..
+            } => {
+                let involved = if nodes.contains(&self.node) {
+                    self.streams.insert(*stream_id);
+                    true
+                } else {
+                    self.streams.remove(stream_id)
+                };
+                involved.then(|| log.clone())
+            }
+            RegistryEvent::NodePlacementUpdated {
+                stream_id,
+                node,
+                is_added,
+            } if *node == self.node => {
+                if *is_added {
+                    self.streams.insert(*stream_id);
+                } else {
+                    self.streams.remove(stream_id);
+                }
+                Some(log.clone())
+            }
+            RegistryEvent::MiniblockBatch(_) | RegistryEvent::MiniblockUpdateFailed { .. } => {
+                log.filter_streams(|stream_id| self.streams.contains(stream_id))
+            }
+            _ => log.event.involves_node(&self.node).then(|| log.clone()),
+        }
+    }
+}
+
+/// Registry event as printed by the watch command, miniblock batches are printed as one row
+/// per stream.
+#[derive(Serialize)]
+struct WatchEvent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_id: Option<FixedBytes<32>>,
+    log_index: u64,
+    #[serde(flatten)]
+    update: StreamUpdate,
+}
+
+impl WatchEvent {
+    const COLUMNS: [&str; 9] = [
+        "event",
+        "river_block",
+        "transaction",
+        "log_index",
+        "stream_id",
+        "miniblock_num",
+        "miniblock_hash",
+        "nodes",
+        "replication_factor",
+    ];
+
+    fn from_log(log: RegistryLog) -> Vec<WatchEvent> {
+        let event = |stream_id, log: RegistryLog| WatchEvent {
+            stream_id,
+            log_index: log.log_index,
+            update: log.into(),
+        };
+
+        match &log.event {
+            RegistryEvent::MiniblockBatch(updates) => updates
+                .iter()
+                .map(|update| {
+                    let log = RegistryLog {
+                        event: RegistryEvent::MiniblockBatch(vec![update.clone()]),
+                        ..log.clone()
+                    };
+                    event(Some(update.stream_id), log)
+                })
+                .collect(),
+            RegistryEvent::Allocated { stream_id, .. }
+            | RegistryEvent::Created { stream_id, .. }
+            | RegistryEvent::PlacementUpdated { stream_id, .. }
+            | RegistryEvent::NodePlacementUpdated { stream_id, .. }
+            | RegistryEvent::MiniblockUpdateFailed { stream_id, .. } => {
+                vec![event(Some(*stream_id), log)]
+            }
+            _ => vec![event(None, log)],
+        }
+    }
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.update.event.to_string(),
+            self.update.river_block.to_string(),
+            self.update.transaction.to_string(),
+            self.log_index.to_string(),
+            output::opt(&self.stream_id),
+            output::opt(&self.update.miniblock_num),
+            output::opt(&self.update.miniblock_hash),
+            self.update
+                .nodes
+                .as_deref()
+                .map(output::list)
+                .unwrap_or_default(),
+            output::opt(&self.update.replication_factor),
+        ]
+    }
+}
+
+/// Identifies a log within a block.
+type LogKey = (FixedBytes<32>, u64);
+
+/// Result of applying re-read blocks to the follower.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Applied {
+    /// logs that were not seen before
+    new: Vec<RegistryLog>,
+    /// previously seen logs that are no longer part of the chain
+    removed: Vec<(u64, LogKey)>,
+}
+
+/// Keeps track of the logs in the last `reorg_depth` blocks. Every poll these blocks are read
+/// again, logs that weren't seen before are new and seen logs that are missing are reorged out.
+#[derive(Debug)]
+struct Follower {
+    start_block: u64,
+    next_block: u64,
+    reorg_depth: u64,
+    seen: BTreeMap<u64, BTreeSet<LogKey>>,
+}
+
+impl Follower {
+    fn new(start_block: u64, reorg_depth: u64) -> Self {
+        Follower {
+            start_block,
+            next_block: start_block,
+            reorg_depth,
+            seen: BTreeMap::new(),
+        }
+    }
+
+    /// Blocks to read for the given chain head, `None` if there are no new blocks.
+    fn range(&self, head: u64) -> Option<RangeInclusive<u64>> {
+        if head < self.next_block {
+            return None;
+        }
+        let from = self
+            .next_block
+            .saturating_sub(self.reorg_depth)
+            .max(self.start_block);
+        Some(from..=head)
+    }
+
+    /// Apply all logs that were read for the given range. The blocks of a poll can be applied
+    /// in consecutive ranges.
+    fn apply(&mut self, range: RangeInclusive<u64>, logs: Vec<RegistryLog>) -> Applied {
+        let mut applied = Applied::default();
+        let mut read: BTreeMap<u64, BTreeSet<LogKey>> = BTreeMap::new();
+
+        for log in logs {
+            let key = (log.block_hash, log.log_index);
+            let seen = self
+                .seen
+                .get(&log.block_number)
+                .is_some_and(|seen| seen.contains(&key));
+            if read.entry(log.block_number).or_default().insert(key) && !seen {
+                applied.new.push(log);
+            }
+        }
+
+        for (block, keys) in self.seen.range(range.clone()) {
+            let current = read.get(block);
+            for key in keys {
+                if !current.is_some_and(|current| current.contains(key)) {
+                    applied.removed.push((*block, *key));
+                }
+            }
+        }
+
+        let (from, to) = range.into_inner();
+        self.seen.retain(|block, _| *block < from || *block > to);
+        self.seen.extend(read);
+        self.next_block = self.next_block.max(to + 1);
+
+        let keep_from = self.next_block.saturating_sub(self.reorg_depth);
+        self.seen = self.seen.split_off(&keep_from);
+
+        applied
+    }
+}
+
+/// Source of new chain heads.
+enum Heads {
+    Poll(Duration),
+    Subscription(Subscription<Header>),
+}
+
+impl Heads {
+    /// Wait for the next chain head, `None` means the head must be requested from the client.
+    /// Missed heads aren't a problem, the follower reads all blocks after the last processed
+    /// block.
+    async fn next<P: Provider>(&mut self, client: &RegistryClient<P>) -> eyre::Result<Option<u64>> {
+        match self {
+            Heads::Poll(interval) => {
+                tokio::time::sleep(*interval).await;
+                Ok(None)
+            }
+            Heads::Subscription(subscription) => match subscription.recv().await {
+                Ok(header) => Ok(Some(header.number)),
+                Err(RecvError::Lagged(missed)) => {
+                    eprintln!("block subscription lagged behind by {} heads", missed);
+                    Ok(None)
+                }
+                Err(RecvError::Closed) => {
+                    eprintln!("block subscription closed, subscribing again");
+                    *subscription = client
+                        .provider()
+                        .subscribe_blocks()
+                        .await
+                        .wrap_err("Failed to subscribe to new blocks")?;
+                    Ok(None)
+                }
+            },
+        }
+    }
+}
+
+/// Options for the watch command.
+#[derive(Debug)]
+pub(crate) struct WatchOpts {
+    pub filter: EventFilter,
+    pub from_block: Option<u64>,
+    pub reorg_depth: u64,
+    pub poll_interval: Duration,
+}
+
+/// Print registry events as they are mined until interrupted. WebSocket RPC URLs subscribe to
+/// new blocks, other URLs are polled.
+pub(crate) async fn watch(
+    cfg: &config::Config,
+    opts: WatchOpts,
+    out: OutputFormat,
+) -> eyre::Result<()> {
+    let index = index::open(cfg)?;
+    let url = cfg.river_rpc_url.as_str();
+    if url.starts_with("ws://") || url.starts_with("wss://") {
+        let provider = cfg
+            .river_chain_ws_provider()
+            .await
+            .wrap_err("Failed to connect to River chain")?;
+        let subscription = provider
+            .subscribe_blocks()
+            .await
+            .wrap_err("Failed to subscribe to new blocks")?;
+        let client = cfg.registry_client_with(provider);
+        follow(client, index, Heads::Subscription(subscription), opts, out).await
+    } else {
+        let client = cfg.registry_client()?;
+        follow(client, index, Heads::Poll(opts.poll_interval), opts, out).await
+    }
+}
+
+async fn follow<P: Provider>(
+    client: RegistryClient<P>,
+    index: Option<Index>,
+    mut heads: Heads,
+    opts: WatchOpts,
+    out: OutputFormat,
+) -> eyre::Result<()> {
+    let start_block = match opts.from_block {
+        Some(block) => block,
+        None => client.block_number().await? + 1,
+    };
+
+    let mut node_streams = match opts.filter.node {
+        Some(node) => Some(NodeStreams::load(&client, node, start_block.saturating_sub(1)).await?),
+        None => None,
+    };
+    let mut follower = Follower::new(start_block, opts.reorg_depth);
+    let mut writer = RowWriter::new(out, &WatchEvent::COLUMNS);
+
+    eprintln!("watching registry events from block {}", start_block);
+
+    // the ctrl-c handler is installed when the future is first polled, it races the whole
+    // follow loop so an interrupt during a long catch-up fetch isn't lost
+    let result = tokio::select! {
+        result = follow_heads(&client, index.as_ref(), &mut heads, &opts.filter, &mut node_streams, &mut follower, &mut writer) => result,
+        interrupted = tokio::signal::ctrl_c() => interrupted.wrap_err("Failed to listen for ctrl-c"),
+    };
+    eprintln!("resume with --from-block {}", follower.next_block);
+    result
+}
+
+/// Print the events of each new head, only returns on errors. The blocks are read and printed
+/// in chunks, catching up from an old block doesn't hold all events in memory.
+async fn follow_heads<P: Provider>(
+    client: &RegistryClient<P>,
+    index: Option<&Index>,
+    heads: &mut Heads,
+    filter: &EventFilter,
+    node_streams: &mut Option<NodeStreams>,
+    follower: &mut Follower,
+    writer: &mut RowWriter,
+) -> eyre::Result<()> {
+    let mut head = None;
+    loop {
+        let latest = match head.take() {
+            Some(head) => head,
+            None => client.block_number().await?,
+        };
+
+        if let Some(range) = follower.range(latest) {
+            let chunks = index::event_stream(index, client, Selection::All, range)?;
+            let mut chunks = std::pin::pin!(chunks);
+            while let Some(chunk) = chunks
+                .try_next()
+                .await
+                .wrap_err("Failed to get registry events")?
+            {
+                let applied = follower.apply(chunk.range, chunk.events);
+
+                for (block, (_, log_index)) in &applied.removed {
+                    eprintln!("reorg: log {} in block {} removed", log_index, block);
+                }
+
+                for log in &applied.new {
+                    let log = match node_streams {
+                        Some(node_streams) => node_streams.apply(log),
+                        None => Some(log.clone()),
+                    };
+                    let Some(log) = log.and_then(|log| filter.apply(&log)) else {
+                        continue;
+                    };
+                    for event in WatchEvent::from_log(log) {
+                        writer.write(&event, &event.row())?;
+                    }
+                }
+            }
+        }
+
+        head = heads.next(client).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use towns_protocol_registry::MiniblockUpdate;
+
+    fn log(block_number: u64, block_hash: u8, log_index: u64) -> RegistryLog {
+        let mut stream_id = FixedBytes::<32>::ZERO;
+        stream_id[0] = towns_protocol_types::CHANNEL_STREAM_ID_PREFIX;
+        RegistryLog {
+            block_number,
+            block_hash: FixedBytes::repeat_byte(block_hash),
+            transaction_hash: FixedBytes::ZERO,
+            log_index,
+            event: RegistryEvent::MiniblockBatch(vec![MiniblockUpdate {
+                stream_id,
+                last_miniblock_hash: FixedBytes::ZERO,
+                last_miniblock_num: block_number,
+                is_sealed: false,
+            }]),
+        }
+    }
+
+    #[test]
+    fn follow_new_blocks() {
+        let mut follower = Follower::new(10, 2);
+        assert_eq!(Some(10..=11), follower.range(11));
+
+        let applied = follower.apply(10..=11, vec![log(10, 1, 0), log(11, 1, 0)]);
+        assert_eq!(vec![log(10, 1, 0), log(11, 1, 0)], applied.new);
+        assert!(applied.removed.is_empty());
+
+        // no new blocks
+        assert_eq!(None, follower.range(11));
+
+        // recent blocks are read again but not reported twice
+        assert_eq!(Some(10..=13), follower.range(13));
+        let applied = follower.apply(10..=13, vec![log(10, 1, 0), log(11, 1, 0), log(13, 1, 0)]);
+        assert_eq!(vec![log(13, 1, 0)], applied.new);
+        assert!(applied.removed.is_empty());
+
+        assert_eq!(Some(12..=14), follower.range(14));
+    }
+
+    #[test]
+    fn follow_in_chunks() {
+        let mut follower = Follower::new(10, 3);
+        follower.apply(10..=12, vec![log(11, 1, 0), log(12, 1, 0)]);
+
+        // the re-read of blocks 10 to 14 arrives in two chunks, the first chunk must not forget
+        // the logs seen in the blocks of the second chunk
+        let applied = follower.apply(10..=11, vec![log(11, 1, 0)]);
+        assert_eq!(Applied::default(), applied);
+        assert_eq!(13, follower.next_block);
+
+        let applied = follower.apply(12..=14, vec![log(12, 1, 0), log(14, 1, 0)]);
+        assert_eq!(vec![log(14, 1, 0)], applied.new);
+        assert!(applied.removed.is_empty());
+        assert_eq!(Some(12..=15), follower.range(15));
+    }
+
+    #[test]
+    fn follow_reorg() {
+        let mut follower = Follower::new(10, 3);
+        follower.apply(10..=12, vec![log(11, 1, 0), log(12, 1, 0), log(12, 1, 1)]);
+
+        // block 12 is replaced by a block with a single log
+        let applied = follower.apply(10..=13, vec![log(11, 1, 0), log(12, 2, 0)]);
+        assert_eq!(vec![log(12, 2, 0)], applied.new);
+        assert_eq!(
+            vec![
+                (12, (FixedBytes::repeat_byte(1), 0)),
+                (12, (FixedBytes::repeat_byte(1), 1))
+            ],
+            applied.removed
+        );
+    }
+
+    #[test]
+    fn filter_events() {
+        let stream_log = log(10, 1, 0);
+        let node_log = RegistryLog {
+            event: RegistryEvent::NodeRemoved {
+                node: Address::repeat_byte(1),
+            },
+            ..log(10, 1, 1)
+        };
+
+        let all = EventFilter::default();
+        assert!(all.apply(&stream_log).is_some());
+        assert!(all.apply(&node_log).is_some());
+
+        let channels = EventFilter {
+            stream_types: vec![StreamKind::Channel],
+            ..Default::default()
+        };
+        assert!(channels.apply(&stream_log).is_some());
+        assert!(channels.apply(&node_log).is_none());
+    }
+
+    #[test]
+    fn filter_node_streams() {
+        let node = Address::repeat_byte(1);
+        let stream_log = log(10, 1, 0);
+        let RegistryEvent::MiniblockBatch(updates) = &stream_log.event else {
+            unreachable!()
+        };
+        let stream_id = updates[0].stream_id;
+        let placement = |nodes| RegistryLog {
+            event: RegistryEvent::PlacementUpdated {
+                stream_id,
+                nodes,
+                replication_factor: 1,
+            },
+            ..log(10, 1, 1)
+        };
+
+        let mut node_streams = NodeStreams {
+            node,
+            streams: HashSet::new(),
+        };
+        assert!(node_streams.apply(&stream_log).is_none());
+        assert!(
+            node_streams
+                .apply(&RegistryLog {
+                    event: RegistryEvent::NodeRemoved { node },
+                    ..log(10, 1, 2)
+                })
+                .is_some()
+        );
+
+        // the stream is placed on the node, its miniblock updates match from now on
+        assert!(node_streams.apply(&placement(vec![node])).is_some());
+        assert!(node_streams.apply(&stream_log).is_some());
+
+        // the placement that removes the node matches, later updates don't
+        assert!(
+            node_streams
+                .apply(&placement(vec![Address::repeat_byte(2)]))
+                .is_some()
+        );
+        assert!(node_streams.apply(&stream_log).is_none());
+        assert!(
+            node_streams
+                .apply(&placement(vec![Address::repeat_byte(2)]))
+                .is_none()
+        );
+    }
+}
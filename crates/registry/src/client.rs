@@ -0,0 +1,913 @@
+use crate::{Inception, LogChunk, MAX_LOG_RANGE_GROWTH, concurrent_log_stream};
+use alloy_primitives::{Address, B256, FixedBytes, U256};
+use alloy_provider::Provider;
+use alloy_rpc_types::{BlockId, BlockNumberOrTag, Filter, Log};
+use alloy_sol_types::SolEvent;
+use futures::{Stream, StreamExt, TryStreamExt};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Mutex};
+use towns_protocol_contracts::{
+    MINIBLOCK_UPDATE_FAILED_SIGNATURE, NodeRegistry, RegistryEvent, RegistryLog,
+    STREAM_EVENT_SIGNATURES, StreamEventType, StreamsRegistry,
+};
+use towns_protocol_types::{StreamId, TownsError};
+
+/// Default number of river blocks that are requested in a single get_logs call.
+pub const DEFAULT_LOG_RANGE: u64 = 2_500;
+
+/// RegistryClient queries the stream and node registry over any alloy provider.
+#[derive(Debug, Clone)]
+pub struct RegistryClient<P> {
+    provider: P,
+    address: Address,
+    deployment_block: u64,
+    log_range: u64,
+    concurrency: usize,
+    /// block timestamps that were already fetched, shared between clones
+    block_times: Arc<Mutex<BTreeMap<u64, u64>>>,
+}
+
+impl<P: Provider> RegistryClient<P> {
+    /// Create a client for the registry deployed at `address` in river block `deployment_block`.
+    pub fn new(provider: P, address: Address, deployment_block: u64) -> Self {
+        RegistryClient {
+            provider,
+            address,
+            deployment_block,
+            log_range: DEFAULT_LOG_RANGE,
+            concurrency: 1,
+            block_times: Arc::default(),
+        }
+    }
+
+    /// Set the number of river blocks that are requested in a single get_logs call.
+    pub fn with_log_range(mut self, log_range: u64) -> Self {
+        self.log_range = log_range.max(1);
+        self
+    }
+
+    /// Set the number of get_logs calls that run at the same time during long scans.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn provider(&self) -> &P {
+        &self.provider
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    pub fn deployment_block(&self) -> u64 {
+        self.deployment_block
+    }
+
+    fn streams_registry(&self) -> StreamsRegistry::StreamsRegistryInstance<&P> {
+        StreamsRegistry::new(self.address, &self.provider)
+    }
+
+    fn node_registry(&self) -> NodeRegistry::NodeRegistryInstance<&P> {
+        NodeRegistry::new(self.address, &self.provider)
+    }
+
+    /// Latest river block number.
+    pub async fn block_number(&self) -> Result<u64, TownsError> {
+        self.provider
+            .get_block_number()
+            .await
+            .map_err(TownsError::from)
+    }
+
+    /// Number of the river block with the given tag, e.g. finalized or safe.
+    pub async fn block_number_by_tag(&self, tag: BlockNumberOrTag) -> Result<u64, TownsError> {
+        let block = self
+            .provider
+            .get_block_by_number(tag)
+            .await
+            .map_err(TownsError::from)?
+            .ok_or(TownsError::NotFound)?;
+        Ok(block.header.number)
+    }
+
+    /// Timestamp of the river block in seconds since the unix epoch. Timestamps are cached, so
+    /// asking for the same block again doesn't result in another RPC call.
+    pub async fn block_timestamp(&self, block: u64) -> Result<u64, TownsError> {
+        if let Some(timestamp) = self.cached_block_times().get(&block) {
+            return Ok(*timestamp);
+        }
+
+        let timestamp = self
+            .provider
+            .get_block_by_number(BlockNumberOrTag::Number(block))
+            .await
+            .map_err(TownsError::from)?
+            .ok_or(TownsError::NotFound)?
+            .header
+            .timestamp;
+        self.cached_block_times().insert(block, timestamp);
+        Ok(timestamp)
+    }
+
+    /// Timestamps of the given river blocks, fetching up to `concurrency` blocks at the same time.
+    pub async fn block_timestamps(
+        &self,
+        blocks: impl IntoIterator<Item = u64>,
+    ) -> Result<HashMap<u64, u64>, TownsError> {
+        futures::stream::iter(blocks)
+            .map(|block| async move { Ok((block, self.block_timestamp(block).await?)) })
+            .buffer_unordered(self.concurrency)
+            .try_collect()
+            .await
+    }
+
+    /// Account that sent the transaction, e.g. the node that submitted a miniblock.
+    pub async fn transaction_sender(&self, transaction: B256) -> Result<Address, TownsError> {
+        let transaction = self
+            .provider
+            .get_transaction_by_hash(transaction)
+            .await
+            .map_err(TownsError::from)?
+            .ok_or(TownsError::NotFound)?;
+        Ok(transaction.inner.signer())
+    }
+
+    /// Senders of the given transactions, fetching up to `concurrency` transactions at the same
+    /// time.
+    pub async fn transaction_senders(
+        &self,
+        transactions: impl IntoIterator<Item = B256>,
+    ) -> Result<HashMap<B256, Address>, TownsError> {
+        futures::stream::iter(transactions)
+            .map(|transaction| async move {
+                Ok((transaction, self.transaction_sender(transaction).await?))
+            })
+            .buffer_unordered(self.concurrency)
+            .try_collect()
+            .await
+    }
+
+    /// Latest river block at or before `head` with a timestamp at or before `timestamp`.
+    /// Returns [`TownsError::NotFound`] if the timestamp is before the first block.
+    ///
+    /// The search starts from the closest blocks with a cached timestamp, resolving a series of
+    /// nearby timestamps only costs a few RPC calls each.
+    pub async fn block_at(&self, timestamp: u64, head: u64) -> Result<u64, TownsError> {
+        let (low, high) = {
+            let cached = self.cached_block_times();
+            let low = cached
+                .range(..=head)
+                .rev()
+                .find(|(_, block_time)| **block_time <= timestamp)
+                .map_or(0, |(block, _)| *block);
+            let high = cached
+                .range(low..=head)
+                .find(|(_, block_time)| **block_time > timestamp)
+                .map_or(head, |(block, _)| *block);
+            (low, high)
+        };
+        find_block_at(timestamp, low, high, |block| self.block_timestamp(block)).await
+    }
+
+    fn cached_block_times(&self) -> std::sync::MutexGuard<'_, BTreeMap<u64, u64>> {
+        // the cache is always left consistent, it's safe to use after a panic
+        self.block_times
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Stream record at the given river block, returns [`TownsError::NotFound`] if the stream
+    /// doesn't exist.
+    pub async fn stream_at(
+        &self,
+        stream_id: &StreamId,
+        block: BlockId,
+    ) -> Result<StreamsRegistry::Stream, TownsError> {
+        self.streams_registry()
+            .getStream(stream_id.as_fixed_bytes32())
+            .block(block)
+            .call()
+            .await
+            .map_err(registry_error)
+    }
+
+    /// Total number of streams at the given river block.
+    pub async fn stream_count(&self, block: BlockId) -> Result<U256, TownsError> {
+        self.streams_registry()
+            .getStreamCount()
+            .block(block)
+            .call()
+            .await
+            .map_err(registry_error)
+    }
+
+    /// Page of at most `limit` stream records starting at index `start` at the given river block,
+    /// only the streams placed on `node` if given. The returned flag is true when this is the
+    /// last page.
+    pub async fn streams_page(
+        &self,
+        node: Option<Address>,
+        start: u64,
+        limit: u64,
+        block: BlockId,
+    ) -> Result<(Vec<StreamsRegistry::StreamWithId>, bool), TownsError> {
+        let registry = self.streams_registry();
+        let stop = U256::from(start.saturating_add(limit));
+        let start = U256::from(start);
+
+        match node {
+            Some(node) => {
+                let streams = registry
+                    .getPaginatedStreamsOnNode(node, start, stop)
+                    .block(block)
+                    .call()
+                    .await
+                    .map_err(registry_error)?;
+                // the node variant doesn't report the last page, it returns a short page instead
+                let last = (streams.len() as u64) < limit;
+                Ok((streams, last))
+            }
+            None => {
+                let page = registry
+                    .getPaginatedStreams(start, stop)
+                    .block(block)
+                    .call()
+                    .await
+                    .map_err(registry_error)?;
+                Ok((page._0, page._1))
+            }
+        }
+    }
+
+    /// Number of streams placed on the node at the given river block.
+    pub async fn stream_count_on_node(
+        &self,
+        node: Address,
+        block: BlockId,
+    ) -> Result<U256, TownsError> {
+        self.streams_registry()
+            .getStreamCountOnNode(node)
+            .block(block)
+            .call()
+            .await
+            .map_err(registry_error)
+    }
+
+    /// All registered nodes at the given river block.
+    pub async fn nodes(&self, block: BlockId) -> Result<Vec<NodeRegistry::Node>, TownsError> {
+        self.node_registry()
+            .getAllNodes()
+            .block(block)
+            .call()
+            .await
+            .map_err(registry_error)
+    }
+
+    /// Registered node at the given river block, returns [`TownsError::NotFound`] if the node
+    /// isn't registered.
+    pub async fn node(
+        &self,
+        node: Address,
+        block: BlockId,
+    ) -> Result<NodeRegistry::Node, TownsError> {
+        let registry = self.node_registry();
+        let is_node = registry
+            .isNode(node)
+            .block(block)
+            .call()
+            .await
+            .map_err(registry_error)?;
+
+        if !is_node {
+            return Err(TownsError::NotFound);
+        }
+
+        registry
+            .getNode(node)
+            .block(block)
+            .call()
+            .await
+            .map_err(registry_error)
+    }
+
+    /// Stream record at the given river block together with the nodes the stream is placed on.
+    pub async fn stream_with_nodes(
+        &self,
+        stream_id: &StreamId,
+        block: BlockId,
+    ) -> Result<(StreamsRegistry::Stream, Vec<NodeRegistry::Node>), TownsError> {
+        let stream = self.stream_at(stream_id, block).await?;
+        let registry = self.node_registry();
+
+        let mut nodes = Vec::with_capacity(stream.nodes.len());
+        for node in &stream.nodes {
+            nodes.push(
+                registry
+                    .getNode(*node)
+                    .block(block)
+                    .call()
+                    .await
+                    .map_err(registry_error)?,
+            );
+        }
+
+        Ok((stream, nodes))
+    }
+
+    /// Stream the registry logs in the given block range as chunks in block order. The block
+    /// range of each get_logs call starts at the log range and adapts to the RPC node limits,
+    /// with a concurrency above 1 multiple ranges are fetched at the same time.
+    pub fn log_chunks(
+        &self,
+        range: RangeInclusive<u64>,
+    ) -> impl Stream<Item = Result<LogChunk, TownsError>> + '_ {
+        self.filtered_log_chunks(Filter::new(), range)
+    }
+
+    /// Stream the registry logs that can be about a stream, node registry events are filtered
+    /// out by the RPC node. See [`RegistryClient::log_chunks`].
+    ///
+    /// No registry event has the stream id as indexed topic, so the RPC node can't filter the
+    /// logs for a single stream. `StreamUpdated` carries almost all stream traffic, in practice
+    /// this reads nearly every registry log in the range. Per stream history over long ranges
+    /// needs the local index, it keeps the logs per stream.
+    pub fn stream_log_chunks(
+        &self,
+        range: RangeInclusive<u64>,
+    ) -> impl Stream<Item = Result<LogChunk, TownsError>> + '_ {
+        let filter = Filter::new().event_signature(STREAM_EVENT_SIGNATURES.to_vec());
+        self.filtered_log_chunks(filter, range)
+    }
+
+    /// Stream the logs for rejected miniblock registrations. See [`RegistryClient::log_chunks`].
+    pub fn failure_log_chunks(
+        &self,
+        range: RangeInclusive<u64>,
+    ) -> impl Stream<Item = Result<LogChunk, TownsError>> + '_ {
+        let filter = Filter::new().event_signature(MINIBLOCK_UPDATE_FAILED_SIGNATURE);
+        self.filtered_log_chunks(filter, range)
+    }
+
+    /// Stream the registry logs that match the topics of the filter, see
+    /// [`RegistryClient::log_chunks`].
+    pub fn filtered_log_chunks(
+        &self,
+        filter: Filter,
+        range: RangeInclusive<u64>,
+    ) -> impl Stream<Item = Result<LogChunk, TownsError>> + '_ {
+        concurrent_log_stream(
+            &self.provider,
+            filter.address(self.address),
+            range,
+            self.log_range,
+            self.concurrency,
+        )
+    }
+
+    /// All registry logs in the given block range.
+    pub async fn logs(&self, range: RangeInclusive<u64>) -> Result<Vec<Log>, TownsError> {
+        self.log_chunks(range)
+            .try_fold(Vec::new(), |mut logs, chunk| async move {
+                logs.extend(chunk.logs);
+                Ok(logs)
+            })
+            .await
+    }
+
+    /// Inception of the stream, searched in the given block range. Returns
+    /// [`TownsError::NotFound`] if the stream doesn't exist at the end of the range.
+    ///
+    /// Ranges that can be covered in a few get_logs calls are scanned for the logs that allocate
+    /// or create a stream. Larger ranges are bisected on the stream record to find the inception
+    /// block, after which only that block is scanned.
+    pub async fn inception(
+        &self,
+        stream_id: &StreamId,
+        range: RangeInclusive<u64>,
+    ) -> Result<Inception, TownsError> {
+        let blocks = range.end().saturating_sub(*range.start()).saturating_add(1);
+        if blocks <= self.log_range.saturating_mul(MAX_LOG_RANGE_GROWTH) {
+            return match self.inception_logs(stream_id, range).await? {
+                Some(log) => self.inception_from_log(stream_id, &log).await,
+                None => Err(TownsError::NotFound),
+            };
+        }
+
+        let block = self.inception_block(stream_id, range).await?;
+        match self.inception_logs(stream_id, block..=block).await? {
+            Some(log) => self.inception_from_log(stream_id, &log).await,
+            // the stream exists but the log that allocated it is missing, e.g. a pruned node
+            None => Err(TownsError::NotFound),
+        }
+    }
+
+    /// First block in the range at which the stream record exists. Returns
+    /// [`TownsError::NotFound`] if the stream doesn't exist at the end of the range, other
+    /// errors are returned as is instead of being taken as a missing stream.
+    pub async fn inception_block(
+        &self,
+        stream_id: &StreamId,
+        range: RangeInclusive<u64>,
+    ) -> Result<u64, TownsError> {
+        find_first_block(range, |block| async move {
+            match self
+                .stream_at(stream_id, BlockId::Number(BlockNumberOrTag::Number(block)))
+                .await
+            {
+                Ok(_) => Ok(true),
+                Err(TownsError::NotFound) => Ok(false),
+                Err(err) => Err(err),
+            }
+        })
+        .await
+    }
+
+    /// First log in the range that allocates or creates the stream. Only logs that allocate or
+    /// create a stream are requested.
+    async fn inception_logs(
+        &self,
+        stream_id: &StreamId,
+        range: RangeInclusive<u64>,
+    ) -> Result<Option<RegistryLog>, TownsError> {
+        let id = stream_id.as_fixed_bytes32();
+        for filter in self.inception_filters() {
+            let mut chunks = std::pin::pin!(self.filtered_log_chunks(filter, range.clone()));
+            while let Some(chunk) = chunks.try_next().await? {
+                for log in &chunk.logs {
+                    if let Some(log) = decode_log(log).filter(|log| is_inception(&log.event, &id))
+                    {
+                        return Ok(Some(log));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Filters for the logs that allocate or create a stream, in the unified `StreamUpdated`
+    /// model the event type is an indexed topic.
+    fn inception_filters(&self) -> [Filter; 2] {
+        let event_type = |event_type: StreamEventType| B256::from(U256::from(event_type as u8));
+        [
+            Filter::new()
+                .event_signature(StreamsRegistry::StreamUpdated::SIGNATURE_HASH)
+                .topic1(vec![
+                    event_type(StreamEventType::Allocate),
+                    event_type(StreamEventType::Create),
+                ]),
+            Filter::new().event_signature(vec![
+                StreamsRegistry::StreamAllocated::SIGNATURE_HASH,
+                StreamsRegistry::StreamCreated::SIGNATURE_HASH,
+            ]),
+        ]
+    }
+
+    /// Inception details from the log that allocated or created the stream. The genesis
+    /// miniblock is read from the registry if the log doesn't include it. Returns
+    /// [`TownsError::NotFound`] if the log doesn't allocate or create the stream.
+    pub async fn inception_from_log(
+        &self,
+        stream_id: &StreamId,
+        log: &RegistryLog,
+    ) -> Result<Inception, TownsError> {
+        if !is_inception(&log.event, &stream_id.as_fixed_bytes32()) {
+            return Err(TownsError::NotFound);
+        }
+        let (allocated, nodes, genesis_hash, genesis_miniblock) = match &log.event {
+            RegistryEvent::Allocated {
+                stream_id,
+                nodes,
+                genesis_hash,
+                genesis_miniblock,
+                ..
+            } => (stream_id, nodes, genesis_hash, genesis_miniblock),
+            RegistryEvent::Created {
+                stream_id,
+                nodes,
+                genesis_hash,
+                ..
+            } => (stream_id, nodes, genesis_hash, &None),
+            _ => return Err(TownsError::NotFound),
+        };
+
+        // the unified event model doesn't include the genesis miniblock in the event, it's only
+        // absent when the registry doesn't know the stream, other errors are returned
+        let genesis_miniblock = match genesis_miniblock {
+            Some(genesis_miniblock) => Some(genesis_miniblock.clone()),
+            None => match self
+                .streams_registry()
+                .getStreamWithGenesis(*allocated)
+                .block(BlockId::Number(BlockNumberOrTag::Number(log.block_number)))
+                .call()
+                .await
+                .map_err(registry_error)
+            {
+                Ok(stream) => Some(stream._2),
+                Err(TownsError::NotFound) => None,
+                Err(err) => return Err(err),
+            },
+        };
+
+        Ok(Inception {
+            stream_id: *stream_id,
+            river_block: log.block_number,
+            block_hash: log.block_hash,
+            transaction: log.transaction_hash,
+            nodes: nodes.clone(),
+            genesis_hash: *genesis_hash,
+            genesis_miniblock,
+        })
+    }
+
+    /// All registry events in the given block range in the order they were emitted.
+    pub async fn events(&self, range: RangeInclusive<u64>) -> Result<Vec<RegistryLog>, TownsError> {
+        let mut events = Vec::new();
+        for log in &self.logs(range).await? {
+            events.extend(decode_log(log));
+        }
+        Ok(events)
+    }
+
+    /// All events for the stream in the given block range in the order they were emitted.
+    /// Miniblock batches only contain the updates for the stream.
+    pub async fn history(
+        &self,
+        stream_id: &StreamId,
+        range: RangeInclusive<u64>,
+    ) -> Result<Vec<RegistryLog>, TownsError> {
+        let id = stream_id.as_fixed_bytes32();
+        self.stream_log_chunks(range)
+            .try_fold(Vec::new(), |mut history, chunk| async move {
+                for log in &chunk.logs {
+                    history.extend(decode_log(log).and_then(|log| log.for_stream(&id)));
+                }
+                Ok(history)
+            })
+            .await
+    }
+}
+
+/// Search for the latest block in `low..=high` with a timestamp at or before `timestamp`, `low`
+/// must be 0 or a block with a timestamp at or before `timestamp`. Block timestamps never
+/// decrease, so this holds regardless of drift in the block time.
+///
+/// Blocks are guessed by interpolating between the timestamps of the bounds, which finds the
+/// block in a few steps when the block time is steady. The search falls back to bisection when a
+/// guess doesn't halve the range.
+async fn find_block_at<F, Fut>(
+    timestamp: u64,
+    low: u64,
+    high: u64,
+    timestamp_of: F,
+) -> Result<u64, TownsError>
+where
+    F: Fn(u64) -> Fut,
+    Fut: Future<Output = Result<u64, TownsError>>,
+{
+    let (mut high_time, mut low_time) = (timestamp_of(high).await?, timestamp_of(low).await?);
+    if high_time <= timestamp {
+        return Ok(high);
+    }
+    if low_time > timestamp {
+        return Err(TownsError::NotFound);
+    }
+
+    // invariant: low_time <= timestamp < high_time
+    let (mut low, mut high) = (low, high);
+    let mut interpolate = true;
+    while high - low > 1 {
+        let blocks = high - low;
+        let mid = if interpolate {
+            let offset = u128::from(timestamp - low_time) * u128::from(blocks)
+                / u128::from(high_time - low_time);
+            (low + offset as u64).clamp(low + 1, high - 1)
+        } else {
+            low + blocks / 2
+        };
+
+        let mid_time = timestamp_of(mid).await?;
+        if mid_time <= timestamp {
+            (low, low_time) = (mid, mid_time);
+        } else {
+            (high, high_time) = (mid, mid_time);
+        }
+        interpolate = high - low <= blocks / 2;
+    }
+    Ok(low)
+}
+
+/// Search for the first block in the range for which `exists` returns true, assuming it returns
+/// true for all later blocks. Returns [`TownsError::NotFound`] if it's false for the last block.
+async fn find_first_block<F, Fut>(range: RangeInclusive<u64>, exists: F) -> Result<u64, TownsError>
+where
+    F: Fn(u64) -> Fut,
+    Fut: Future<Output = Result<bool, TownsError>>,
+{
+    let (mut low, mut high) = range.into_inner();
+    if low > high || !exists(high).await? {
+        return Err(TownsError::NotFound);
+    }
+
+    // invariant: exists(high) and !exists(block) for all blocks before low
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if exists(mid).await? {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    Ok(high)
+}
+
+/// Returns true if the event allocates or creates the stream.
+fn is_inception(event: &RegistryEvent, stream_id: &FixedBytes<32>) -> bool {
+    match event {
+        RegistryEvent::Allocated { stream_id: id, .. }
+        | RegistryEvent::Created { stream_id: id, .. } => id == stream_id,
+        _ => false,
+    }
+}
+
+/// Reason the registry contracts revert with when the requested stream or node doesn't exist.
+const NOT_FOUND_REASON: &str = "NOT_FOUND";
+
+/// Classify an error from a registry contract call, reverts because the item doesn't exist are
+/// returned as [`TownsError::NotFound`].
+fn registry_error(err: impl Into<TownsError>) -> TownsError {
+    match err.into() {
+        TownsError::Reverted(Some(reason)) if reason == NOT_FOUND_REASON => TownsError::NotFound,
+        err => err,
+    }
+}
+
+/// Decode a registry event from a log, logs that don't contain a registry event are ignored.
+/// Registry logs with malformed data are returned as [`RegistryEvent::Undecodable`].
+pub fn decode_log(log: &Log) -> Option<RegistryLog> {
+    RegistryLog::decode(log)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{FixedBytes, LogData};
+    use alloy_sol_types::SolType;
+    use towns_protocol_contracts::{SetMiniblock, SetMiniblockArray, StreamEventType};
+
+    fn log(event_type: StreamEventType, data: Vec<u8>) -> Log {
+        let event = StreamsRegistry::StreamUpdated {
+            eventType: event_type as u8,
+            data: data.into(),
+        };
+        Log {
+            inner: alloy_primitives::Log {
+                address: Address::ZERO,
+                data: LogData::from(&event),
+            },
+            block_number: Some(10),
+            transaction_hash: Some(FixedBytes::repeat_byte(0x01)),
+            log_index: Some(3),
+            ..Default::default()
+        }
+    }
+
+    fn stream_id(last: u8) -> StreamId {
+        let mut id = FixedBytes::<32>::ZERO;
+        id[0] = towns_protocol_types::CHANNEL_STREAM_ID_PREFIX;
+        id[31] = last;
+        StreamId::try_from(&id).unwrap()
+    }
+
+    #[test]
+    fn decode_log_metadata() {
+        let data = SetMiniblockArray::abi_encode_params(&vec![SetMiniblock {
+            streamId: stream_id(1).as_fixed_bytes32(),
+            prevMiniBlockHash: FixedBytes::ZERO,
+            lastMiniblockHash: FixedBytes::repeat_byte(0xaa),
+            lastMiniblockNum: 7,
+            isSealed: false,
+        }]);
+
+        let decoded = decode_log(&log(StreamEventType::LastMiniblockBatchUpdated, data)).unwrap();
+
+        assert_eq!(10, decoded.block_number);
+        assert_eq!(FixedBytes::repeat_byte(0x01), decoded.transaction_hash);
+        assert_eq!(3, decoded.log_index);
+        assert_eq!("MiniblockUpdated", decoded.event.name());
+    }
+
+    #[test]
+    fn decode_invalid_event_data() {
+        let decoded = decode_log(&log(StreamEventType::Allocate, vec![0x01])).unwrap();
+        assert_eq!(3, decoded.log_index);
+        assert_eq!("Undecodable", decoded.event.name());
+    }
+
+    #[tokio::test]
+    async fn find_block_by_timestamp() {
+        // 2 second blocks that slow down to 5 second blocks after block 100
+        let timestamp_of = |block: u64| async move {
+            Ok(match block {
+                0..=100 => 1_000 + 2 * block,
+                _ => 1_200 + 5 * (block - 100),
+            })
+        };
+
+        assert_eq!(0, find_block_at(1_000, 0, 500, timestamp_of).await.unwrap());
+        assert_eq!(
+            50,
+            find_block_at(1_100, 0, 500, timestamp_of).await.unwrap()
+        );
+        assert_eq!(
+            50,
+            find_block_at(1_101, 0, 500, timestamp_of).await.unwrap()
+        );
+        assert_eq!(
+            200,
+            find_block_at(1_700, 0, 500, timestamp_of).await.unwrap()
+        );
+        assert_eq!(
+            200,
+            find_block_at(1_704, 0, 500, timestamp_of).await.unwrap()
+        );
+        assert_eq!(
+            500,
+            find_block_at(9_999, 0, 500, timestamp_of).await.unwrap()
+        );
+        assert!(matches!(
+            find_block_at(999, 0, 500, timestamp_of).await,
+            Err(TownsError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn find_stream_inception_block() {
+        let exists = |block: u64| async move { Ok(block >= 42) };
+        assert_eq!(42, find_first_block(0..=100, exists).await.unwrap());
+        assert_eq!(42, find_first_block(42..=42, exists).await.unwrap());
+        assert_eq!(50, find_first_block(50..=100, exists).await.unwrap());
+        assert_eq!(
+            0,
+            find_first_block(0..=10, |_| async { Ok(true) })
+                .await
+                .unwrap()
+        );
+        assert!(matches!(
+            find_first_block(0..=41, exists).await,
+            Err(TownsError::NotFound)
+        ));
+
+        // a failing call is not taken as a missing stream
+        let failing = |block: u64| async move {
+            match block {
+                0..=41 => Ok(false),
+                50 => Err(TownsError::Reverted(None)),
+                _ => Ok(true),
+            }
+        };
+        assert!(matches!(
+            find_first_block(0..=100, failing).await,
+            Err(TownsError::Reverted(None))
+        ));
+    }
+
+    #[tokio::test]
+    async fn interpolate_steady_block_times() {
+        let probes = std::sync::atomic::AtomicUsize::new(0);
+        let timestamp_of = |block: u64| {
+            probes.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            async move { Ok(1_000_000 + 2 * block) }
+        };
+
+        let block = find_block_at(1_000_000 + 2 * 123_456 + 1, 0, 10_000_000, timestamp_of)
+            .await
+            .unwrap();
+        assert_eq!(123_456, block);
+        assert!(probes.into_inner() <= 6);
+    }
+
+    /// Start a stub RPC server that answers get_logs calls with the logs that match the topic
+    /// filter of the request, like an RPC node does.
+    async fn stub_rpc(logs: Vec<Log>) -> String {
+        use axum::Json;
+        use serde_json::{Value, json};
+
+        let matches = |filter: &Value, topic: &FixedBytes<32>| match filter {
+            Value::Null => true,
+            Value::String(expected) => expected.parse::<FixedBytes<32>>().unwrap() == *topic,
+            Value::Array(expected) => expected.iter().any(|expected| {
+                expected
+                    .as_str()
+                    .unwrap()
+                    .parse::<FixedBytes<32>>()
+                    .unwrap()
+                    == *topic
+            }),
+            _ => panic!("unexpected topic filter {}", filter),
+        };
+
+        let app = axum::Router::new().route(
+            "/",
+            axum::routing::post(move |Json(request): Json<Value>| {
+                let filters = request["params"][0]["topics"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default();
+                let result: Vec<Value> = logs
+                    .iter()
+                    .filter(|log| {
+                        filters.iter().enumerate().all(|(i, filter)| {
+                            log.topics()
+                                .get(i)
+                                .is_some_and(|topic| matches(filter, topic))
+                        })
+                    })
+                    .map(|log| serde_json::to_value(log).unwrap())
+                    .collect();
+                let response = json!({"jsonrpc": "2.0", "id": request["id"], "result": result});
+                async move { Json(response) }
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    #[tokio::test]
+    async fn filter_logs_by_event_signature() {
+        let data = SetMiniblockArray::abi_encode_params(&vec![SetMiniblock {
+            streamId: stream_id(1).as_fixed_bytes32(),
+            prevMiniBlockHash: FixedBytes::ZERO,
+            lastMiniblockHash: FixedBytes::repeat_byte(0xaa),
+            lastMiniblockNum: 7,
+            isSealed: false,
+        }]);
+        let miniblocks = log(StreamEventType::LastMiniblockBatchUpdated, data);
+        let failure = Log {
+            inner: alloy_primitives::Log {
+                address: Address::ZERO,
+                data: LogData::from(&StreamsRegistry::StreamLastMiniblockUpdateFailed {
+                    streamId: stream_id(2).as_fixed_bytes32(),
+                    lastMiniblockHash: FixedBytes::ZERO,
+                    lastMiniblockNum: 3,
+                    reason: "BAD_PREV_MINIBLOCK_HASH".to_string(),
+                }),
+            },
+            block_number: Some(11),
+            ..miniblocks.clone()
+        };
+        let node_removed = Log {
+            inner: alloy_primitives::Log {
+                address: Address::ZERO,
+                data: LogData::from(&NodeRegistry::NodeRemoved {
+                    nodeAddress: Address::repeat_byte(0x42),
+                }),
+            },
+            block_number: Some(12),
+            ..miniblocks.clone()
+        };
+
+        let url = stub_rpc(vec![miniblocks, failure, node_removed]).await;
+        let provider = alloy_provider::ProviderBuilder::new().connect_http(url.parse().unwrap());
+        let client = RegistryClient::new(provider, Address::ZERO, 0);
+        let names = |chunks: Vec<LogChunk>| -> Vec<&'static str> {
+            chunks
+                .iter()
+                .flat_map(|chunk| &chunk.logs)
+                .filter_map(decode_log)
+                .map(|log| log.event.name())
+                .collect()
+        };
+
+        let all: Vec<LogChunk> = client.log_chunks(0..=99).try_collect().await.unwrap();
+        assert_eq!(
+            vec!["MiniblockUpdated", "MiniblockUpdateFailed", "NodeRemoved"],
+            names(all)
+        );
+
+        let streams: Vec<LogChunk> = client
+            .stream_log_chunks(0..=99)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(
+            vec!["MiniblockUpdated", "MiniblockUpdateFailed"],
+            names(streams)
+        );
+
+        let failures: Vec<LogChunk> = client
+            .failure_log_chunks(0..=99)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(vec!["MiniblockUpdateFailed"], names(failures));
+
+        let history = client.history(&stream_id(2), 0..=99).await.unwrap();
+        assert_eq!(1, history.len());
+        assert_eq!(11, history[0].block_number);
+    }
+}
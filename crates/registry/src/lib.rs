@@ -0,0 +1,14 @@
+//! towns protocol registry client
+mod client;
+mod logs;
+mod records;
+mod transport;
+
+pub use client::*;
+pub use logs::*;
+pub use records::*;
+pub use towns_protocol_contracts::{
+    MINIBLOCK_UPDATE_FAILED_SIGNATURE, MiniblockUpdate, RegistryEvent, RegistryLog, StreamFlags,
+    StreamMeta, StreamsRegistry,
+};
+pub use transport::*;
@@ -0,0 +1,359 @@
+use alloy_provider::Provider;
+use alloy_rpc_types::{Filter, Log};
+use futures::{Stream, StreamExt, TryStreamExt};
+use std::ops::RangeInclusive;
+use towns_protocol_types::{TownsError, is_log_range_error};
+
+/// Chunks with fewer logs than this grow the block range of the next get_logs call.
+pub const SPARSE_CHUNK_LOGS: usize = 1_000;
+
+/// Maximum factor the block range of a get_logs call grows beyond the configured log range.
+pub const MAX_LOG_RANGE_GROWTH: u64 = 16;
+
+/// Logs emitted in a block range.
+#[derive(Debug, Clone)]
+pub struct LogChunk {
+    pub range: RangeInclusive<u64>,
+    pub logs: Vec<Log>,
+}
+
+/// Block range size for get_logs calls. The range is halved when the RPC node rejects a call
+/// because it covers too many blocks or results, and doubled when calls return few logs, up to
+/// the growth limit.
+///
+/// A rejected range larger than any accepted range is taken as the RPC node block range limit,
+/// the window then only grows to just below it. Rejections of ranges that were accepted before
+/// are caused by a dense region and don't lower the limit, the window grows back once the logs
+/// are sparse again.
+#[derive(Debug, Clone)]
+pub struct LogWindow {
+    size: u64,
+    /// growth limit from the configured log range
+    max: u64,
+    /// largest range that the RPC node accepted
+    accepted: u64,
+    /// smallest range larger than all accepted ranges that the RPC node rejected
+    rejected: Option<u64>,
+}
+
+impl LogWindow {
+    pub fn new(log_range: u64) -> Self {
+        let size = log_range.max(1);
+        LogWindow {
+            size,
+            max: size.saturating_mul(MAX_LOG_RANGE_GROWTH),
+            accepted: 0,
+            rejected: None,
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Largest size the window grows to.
+    pub fn limit(&self) -> u64 {
+        match self.rejected {
+            Some(rejected) => self.max.min(rejected - 1).max(1),
+            None => self.max,
+        }
+    }
+
+    /// Next block range starting at `from` and ending at most at `to`.
+    pub fn next(&self, from: u64, to: u64) -> RangeInclusive<u64> {
+        from..=to.min(from.saturating_add(self.size - 1))
+    }
+
+    /// Shrink the window after the RPC node rejected the given range, returns false if the range
+    /// covers a single block and can't be split.
+    pub fn shrink(&mut self, rejected: &RangeInclusive<u64>) -> bool {
+        let blocks = rejected.end() - rejected.start() + 1;
+        if blocks <= 1 {
+            return false;
+        }
+        if blocks > self.accepted {
+            self.rejected = Some(
+                self.rejected
+                    .map_or(blocks, |rejected| rejected.min(blocks)),
+            );
+        }
+        self.size = blocks / 2;
+        true
+    }
+
+    /// Record that the RPC node accepted the range and returned the given number of logs, the
+    /// window grows when the logs are sparse.
+    pub fn record(&mut self, accepted: &RangeInclusive<u64>, logs: usize) {
+        let blocks = accepted.end() - accepted.start() + 1;
+        self.accepted = self.accepted.max(blocks);
+        if logs < SPARSE_CHUNK_LOGS {
+            self.size = self.size.saturating_mul(2).min(self.limit());
+        }
+    }
+}
+
+/// Stream the logs that match the filter in the given block range as chunks in block order. The
+/// block range of each get_logs call is adjusted by the window.
+pub fn log_stream<P: Provider>(
+    provider: &P,
+    filter: Filter,
+    range: RangeInclusive<u64>,
+    window: LogWindow,
+) -> impl Stream<Item = Result<LogChunk, TownsError>> + '_ {
+    let (from, to) = range.into_inner();
+    let state = (Some(from).filter(|from| *from <= to), window);
+
+    futures::stream::try_unfold(state, move |(from, mut window)| {
+        let filter = filter.clone();
+        async move {
+            let Some(from) = from else {
+                return Ok(None);
+            };
+
+            loop {
+                let chunk = window.next(from, to);
+                let query = filter
+                    .clone()
+                    .from_block(*chunk.start())
+                    .to_block(*chunk.end());
+
+                match provider.get_logs(&query).await {
+                    Ok(logs) => {
+                        window.record(&chunk, logs.len());
+                        let next = chunk.end().checked_add(1).filter(|next| *next <= to);
+                        return Ok(Some((LogChunk { range: chunk, logs }, (next, window))));
+                    }
+                    // retry the same start block with the smaller window
+                    Err(err) if is_log_range_error(&err) && window.shrink(&chunk) => {}
+                    Err(err) => return Err(TownsError::from(err)),
+                }
+            }
+        }
+    })
+}
+
+/// Stream the logs that match the filter in the given block range as chunks in block order,
+/// fetching up to `concurrency` block ranges at the same time. The range is split in segments
+/// that are each fetched with their own window, segments are emitted in block order once all
+/// earlier segments are emitted. The logs are identical to those of [`log_stream`].
+pub fn concurrent_log_stream<P: Provider>(
+    provider: &P,
+    filter: Filter,
+    range: RangeInclusive<u64>,
+    log_range: u64,
+    concurrency: usize,
+) -> impl Stream<Item = Result<LogChunk, TownsError>> + '_ {
+    if concurrency <= 1 {
+        return log_stream(provider, filter, range, LogWindow::new(log_range)).left_stream();
+    }
+
+    // a segment can be fetched in a single call when the window is fully grown
+    let segment_blocks = LogWindow::new(log_range).max;
+    let (from, to) = range.into_inner();
+    let segments = (from <= to)
+        .then(|| {
+            (from..=to)
+                .step_by(segment_blocks as usize)
+                .map(move |start| start..=to.min(start.saturating_add(segment_blocks - 1)))
+        })
+        .into_iter()
+        .flatten();
+
+    futures::stream::iter(segments)
+        .map(move |segment| {
+            log_stream(provider, filter.clone(), segment, LogWindow::new(log_range))
+                .try_collect::<Vec<_>>()
+        })
+        .buffered(concurrency)
+        .map_ok(|chunks| futures::stream::iter(chunks.into_iter().map(Ok)))
+        .try_flatten()
+        .right_stream()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{Address, B256, Bytes, LogData};
+    use alloy_provider::ProviderBuilder;
+    use alloy_rpc_client::ClientBuilder;
+    use axum::Json;
+    use serde_json::{Value, json};
+    use std::sync::{Arc, Mutex};
+
+    /// Start a stub RPC server that emits a log in each block of `dense` and rejects get_logs
+    /// calls with more than `max_results` logs. Returns the url and the requested ranges.
+    async fn stub_rpc(
+        dense: RangeInclusive<u64>,
+        max_results: usize,
+    ) -> (String, Arc<Mutex<Vec<RangeInclusive<u64>>>>) {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+
+        let app = axum::Router::new().route(
+            "/",
+            axum::routing::post(move |Json(request): Json<Value>| {
+                let block = |key: &str| {
+                    let hex = request["params"][0][key].as_str().unwrap();
+                    u64::from_str_radix(hex.trim_start_matches("0x"), 16).unwrap()
+                };
+                let range = block("fromBlock")..=block("toBlock");
+                recorded.lock().unwrap().push(range.clone());
+
+                let logs: Vec<Value> = range
+                    .filter(|block| dense.contains(block))
+                    .map(|block| serde_json::to_value(log(block)).unwrap())
+                    .collect();
+
+                let response = if logs.len() > max_results {
+                    json!({"jsonrpc": "2.0", "id": request["id"], "error": {
+                        "code": -32005,
+                        "message": format!("query returned more than {} results", max_results),
+                    }})
+                } else {
+                    json!({"jsonrpc": "2.0", "id": request["id"], "result": logs})
+                };
+                async move { Json(response) }
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, requests)
+    }
+
+    fn log(block: u64) -> Log {
+        Log {
+            inner: alloy_primitives::Log {
+                address: Address::ZERO,
+                data: LogData::new_unchecked(vec![B256::ZERO], Bytes::new()),
+            },
+            block_number: Some(block),
+            block_hash: Some(B256::ZERO),
+            transaction_hash: Some(B256::ZERO),
+            log_index: Some(0),
+            ..Default::default()
+        }
+    }
+
+    fn provider(url: &str) -> impl Provider {
+        ProviderBuilder::new().connect_client(ClientBuilder::default().http(url.parse().unwrap()))
+    }
+
+    #[tokio::test]
+    async fn split_ranges_with_too_many_results() {
+        let (url, requests) = stub_rpc(100..=199, 30).await;
+        let provider = provider(&url);
+
+        let chunks: Vec<LogChunk> =
+            log_stream(&provider, Filter::new(), 0..=299, LogWindow::new(100))
+                .try_collect()
+                .await
+                .unwrap();
+
+        let blocks: Vec<u64> = chunks
+            .iter()
+            .flat_map(|chunk| chunk.logs.iter().map(|log| log.block_number.unwrap()))
+            .collect();
+        assert_eq!((100..=199).collect::<Vec<_>>(), blocks);
+
+        // chunks are contiguous and cover the whole range
+        assert_eq!(0, *chunks[0].range.start());
+        assert_eq!(299, *chunks.last().unwrap().range.end());
+        for pair in chunks.windows(2) {
+            assert_eq!(pair[0].range.end() + 1, *pair[1].range.start());
+        }
+        assert!(chunks.iter().all(|chunk| chunk.logs.len() <= 30));
+
+        // the window shrinks from 200 to 25 blocks in the dense region and grows back after it
+        let sizes: Vec<u64> = chunks
+            .iter()
+            .map(|chunk| chunk.range.end() - chunk.range.start() + 1)
+            .collect();
+        assert_eq!(vec![100, 25, 25, 25, 50, 75], sizes);
+        assert_eq!(11, requests.lock().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn grow_window_for_sparse_ranges() {
+        let (url, requests) = stub_rpc(5000..=5000, 10).await;
+        let provider = provider(&url);
+
+        let chunks: Vec<LogChunk> =
+            log_stream(&provider, Filter::new(), 0..=1499, LogWindow::new(100))
+                .try_collect()
+                .await
+                .unwrap();
+
+        let sizes: Vec<u64> = chunks
+            .iter()
+            .map(|chunk| chunk.range.end() - chunk.range.start() + 1)
+            .collect();
+        assert_eq!(vec![100, 200, 400, 800], sizes);
+        assert_eq!(4, requests.lock().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn concurrent_logs_match_sequential_logs() {
+        let (url, _) = stub_rpc(100..=1099, 300).await;
+        let provider = provider(&url);
+
+        let block_numbers = |chunks: Vec<LogChunk>| -> Vec<u64> {
+            chunks
+                .iter()
+                .flat_map(|chunk| chunk.logs.iter().map(|log| log.block_number.unwrap()))
+                .collect()
+        };
+
+        let sequential: Vec<LogChunk> =
+            concurrent_log_stream(&provider, Filter::new(), 0..=4999, 50, 1)
+                .try_collect()
+                .await
+                .unwrap();
+        let concurrent: Vec<LogChunk> =
+            concurrent_log_stream(&provider, Filter::new(), 0..=4999, 50, 4)
+                .try_collect()
+                .await
+                .unwrap();
+
+        assert_eq!(0, *concurrent[0].range.start());
+        assert_eq!(4999, *concurrent.last().unwrap().range.end());
+        for pair in concurrent.windows(2) {
+            assert_eq!(pair[0].range.end() + 1, *pair[1].range.start());
+        }
+        assert_eq!(block_numbers(sequential), block_numbers(concurrent));
+    }
+
+    #[test]
+    fn shrink_window() {
+        let mut window = LogWindow::new(8);
+        assert_eq!(10..=17, window.next(10, 100));
+        assert_eq!(10..=12, window.next(10, 12));
+
+        // the node limit is below 8 blocks, grow to just below it
+        assert!(window.shrink(&(10..=17)));
+        assert_eq!(4, window.size());
+        window.record(&(10..=13), 0);
+        assert_eq!(7, window.size());
+        assert_eq!(7, window.limit());
+
+        // a dense region rejects a range that was accepted before, it doesn't lower the limit
+        let mut window = LogWindow::new(8);
+        window.record(&(0..=7), 0);
+        assert_eq!(16, window.size());
+        window.record(&(8..=23), 0);
+        assert!(window.shrink(&(24..=55)));
+        assert_eq!(31, window.limit());
+        assert!(window.shrink(&(24..=39)));
+        assert!(window.shrink(&(24..=31)));
+        assert_eq!(4, window.size());
+        assert_eq!(31, window.limit());
+        for _ in 0..4 {
+            window.record(&(24..=27), 0);
+        }
+        assert_eq!(31, window.size());
+
+        let mut window = LogWindow::new(1);
+        assert!(!window.shrink(&(10..=10)));
+    }
+}
@@ -0,0 +1,14 @@
+use alloy_primitives::{Address, Bytes, FixedBytes};
+use towns_protocol_types::StreamId;
+
+/// Stream allocation as recorded in the stream registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inception {
+    pub stream_id: StreamId,
+    pub river_block: u64,
+    pub block_hash: FixedBytes<32>,
+    pub transaction: FixedBytes<32>,
+    pub nodes: Vec<Address>,
+    pub genesis_hash: FixedBytes<32>,
+    pub genesis_miniblock: Option<Bytes>,
+}
@@ -0,0 +1,351 @@
+use alloy_json_rpc::{RequestPacket, ResponsePacket};
+use alloy_transport::{TransportError, TransportFut};
+use std::num::NonZeroU32;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tower::{Layer, Service};
+use towns_protocol_types::{is_retryable_error_payload, is_retryable_transport_error};
+
+/// Spaces requests evenly so no more than the configured number of requests per second are sent.
+#[derive(Debug)]
+struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(requests_per_second: NonZeroU32) -> Self {
+        RateLimiter {
+            interval: Duration::from_secs(1) / requests_per_second.get(),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait until the next request is allowed to be sent.
+    async fn acquire(&self) {
+        let slot = {
+            let mut next = self.next.lock().await;
+            let slot = (*next).max(Instant::now());
+            *next = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// Transport layer that limits the number of RPC requests per second. Without a limit requests
+/// are passed through unchanged.
+#[derive(Debug, Clone)]
+pub struct RateLimitLayer {
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+impl RateLimitLayer {
+    pub fn new(requests_per_second: Option<NonZeroU32>) -> Self {
+        RateLimitLayer {
+            limiter: requests_per_second.map(|rps| Arc::new(RateLimiter::new(rps))),
+        }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+impl<S> Service<RequestPacket> for RateLimitService<S>
+where
+    S: Service<RequestPacket, Response = ResponsePacket, Error = TransportError>
+        + Send
+        + Clone
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: RequestPacket) -> Self::Future {
+        let limiter = self.limiter.clone();
+        let mut inner = self.inner.clone();
+
+        Box::pin(async move {
+            if let Some(limiter) = limiter {
+                limiter.acquire().await;
+            }
+            inner.call(request).await
+        })
+    }
+}
+
+/// Default number of times a failed request is retried.
+pub const DEFAULT_MAX_RETRIES: u32 = 5;
+
+/// Default delay before the first retry.
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Default upper bound for the delay between retries.
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Determines how often and how long to wait before a failed request is retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// number of retries after the first attempt, 0 disables retries
+    pub max_retries: u32,
+    /// delay before the first retry, doubled for every following retry
+    pub initial_backoff: Duration,
+    /// upper bound for the delay between retries
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: DEFAULT_MAX_RETRIES,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the given retry, starting at 0. The exponential backoff is jittered between
+    /// half and the full delay so clients that failed at the same time don't retry in lockstep.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff);
+        let half = backoff / 2;
+        half + half.mul_f64(rand::random::<f64>())
+    }
+}
+
+/// Transport layer that retries requests that failed because of transient errors such as rate
+/// limiting, connection failures or nodes that are temporarily unavailable. Other errors are
+/// returned immediately.
+#[derive(Debug, Clone)]
+pub struct RetryLayer {
+    policy: RetryPolicy,
+}
+
+impl RetryLayer {
+    pub fn new(policy: RetryPolicy) -> Self {
+        RetryLayer { policy }
+    }
+}
+
+impl<S> Layer<S> for RetryLayer {
+    type Service = RetryService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RetryService {
+            inner,
+            policy: self.policy,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryService<S> {
+    inner: S,
+    policy: RetryPolicy,
+}
+
+impl<S> Service<RequestPacket> for RetryService<S>
+where
+    S: Service<RequestPacket, Response = ResponsePacket, Error = TransportError>
+        + Send
+        + Clone
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: RequestPacket) -> Self::Future {
+        let policy = self.policy;
+        let mut inner = self.inner.clone();
+
+        Box::pin(async move {
+            let mut retry = 0;
+            loop {
+                let result = inner.call(request.clone()).await;
+                let retryable = match &result {
+                    // http transports return json-rpc errors as part of the response
+                    Ok(response) => response.iter_errors().any(is_retryable_error_payload),
+                    Err(err) => is_retryable_transport_error(err),
+                };
+
+                if !retryable || retry >= policy.max_retries {
+                    return result;
+                }
+
+                tokio::time::sleep(policy.backoff(retry)).await;
+                retry += 1;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_provider::{Provider, ProviderBuilder};
+    use alloy_rpc_client::ClientBuilder;
+    use axum::Json;
+    use axum::http::StatusCode;
+    use axum::response::{IntoResponse, Response};
+    use serde_json::{Value, json};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use towns_protocol_types::{ErrorKind, TownsError};
+
+    /// Failure the stub RPC server returns before it starts to answer requests.
+    #[derive(Clone, Copy)]
+    enum Failure {
+        TooManyRequests,
+        Unavailable,
+        LimitExceeded,
+        InvalidParams,
+    }
+
+    /// Start a stub RPC server that answers `eth_blockNumber` after the given failures and
+    /// returns its url together with the number of requests it received.
+    async fn stub_rpc(failures: Vec<Failure>) -> (String, Arc<AtomicUsize>) {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+
+        let app = axum::Router::new().route(
+            "/",
+            axum::routing::post(move |Json(request): Json<Value>| {
+                let attempt = counter.fetch_add(1, Ordering::SeqCst);
+                let failure = failures.get(attempt).copied();
+                async move { respond(request["id"].clone(), failure) }
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, requests)
+    }
+
+    fn respond(id: Value, failure: Option<Failure>) -> Response {
+        let error = |code: i64, message: &str| {
+            Json(json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}}))
+                .into_response()
+        };
+
+        match failure {
+            Some(Failure::TooManyRequests) => StatusCode::TOO_MANY_REQUESTS.into_response(),
+            Some(Failure::Unavailable) => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+            Some(Failure::LimitExceeded) => error(-32005, "limit exceeded"),
+            Some(Failure::InvalidParams) => error(-32602, "invalid params"),
+            None => Json(json!({"jsonrpc": "2.0", "id": id, "result": "0x2a"})).into_response(),
+        }
+    }
+
+    fn provider(url: &str, max_retries: u32) -> impl Provider {
+        let policy = RetryPolicy {
+            max_retries,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+        };
+        let client = ClientBuilder::default()
+            .layer(RetryLayer::new(policy))
+            .layer(RateLimitLayer::new(None))
+            .http(url.parse().unwrap());
+        ProviderBuilder::new().connect_client(client)
+    }
+
+    #[tokio::test]
+    async fn retry_transient_errors() {
+        let failures = vec![
+            Failure::TooManyRequests,
+            Failure::Unavailable,
+            Failure::LimitExceeded,
+        ];
+        let (url, requests) = stub_rpc(failures).await;
+
+        assert_eq!(42, provider(&url, 3).get_block_number().await.unwrap());
+        assert_eq!(4, requests.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn give_up_after_max_retries() {
+        let (url, requests) = stub_rpc(vec![Failure::TooManyRequests; 3]).await;
+
+        let err = TownsError::from(provider(&url, 2).get_block_number().await.unwrap_err());
+        assert_eq!(ErrorKind::RateLimited, err.kind());
+        assert_eq!(3, requests.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn dont_retry_permanent_errors() {
+        let (url, requests) = stub_rpc(vec![Failure::InvalidParams]).await;
+
+        let err = TownsError::from(provider(&url, 3).get_block_number().await.unwrap_err());
+        assert!(!err.is_retryable());
+        assert_eq!(1, requests.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn limit_requests_per_second() {
+        let (url, _) = stub_rpc(vec![]).await;
+        let client = ClientBuilder::default()
+            .layer(RateLimitLayer::new(NonZeroU32::new(20)))
+            .http(url.parse().unwrap());
+        let provider = ProviderBuilder::new().connect_client(client);
+
+        let start = std::time::Instant::now();
+        for _ in 0..3 {
+            provider.get_block_number().await.unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn jittered_exponential_backoff() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+        };
+
+        for (retry, max) in [(0, 100), (1, 200), (2, 400), (3, 800), (4, 1000), (9, 1000)] {
+            let backoff = policy.backoff(retry);
+            assert!(
+                backoff >= Duration::from_millis(max / 2),
+                "{retry}: {backoff:?}"
+            );
+            assert!(
+                backoff <= Duration::from_millis(max),
+                "{retry}: {backoff:?}"
+            );
+        }
+    }
+}
@@ -1,4 +1,7 @@
+use alloy_json_rpc::{ErrorPayload, RpcError};
 use alloy_primitives::FixedBytes;
+use alloy_sol_types::SolError;
+use alloy_transport::{TransportError, TransportErrorKind};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -7,14 +10,289 @@ pub enum TownsError {
     InvalidArgument(&'static str),
     #[error("invalid {0} value {1}")]
     InvalidArgumentWithValue(&'static str, String),
+    #[error("invalid stream id length for prefix {0:#04x} exp{1} got{2}")]
+    InvalidStreamIdLength(u8, usize, usize),
+    #[error("unknown stream id prefix {0:#04x}")]
+    UnknownStreamIdPrefix(u8),
     #[error("invalid stream updated event {0} tx={1} log_idx={2}")]
     InvalidStreamUpdatedEvent(String, FixedBytes<32>, u64),
     #[error("invalid previous miniblock hash exp{0} got{1}")]
     InvalidPreviousMiniblockHash(FixedBytes<32>, FixedBytes<32>),
     #[error("invalid previous miniblock num exp{0} got{1}")]
     InvalidPreviousMiniblockNum(u64, u64),
+    #[error("invalid miniblock hash exp{0} got{1}")]
+    InvalidMiniblockHash(FixedBytes<32>, FixedBytes<32>),
+    #[error("invalid event hash exp{0} got{1}")]
+    InvalidEventHash(FixedBytes<32>, FixedBytes<32>),
     #[error("not found")]
     NotFound,
-    #[error("contract call failed")]
-    ContractCallFailed(alloy_contract::Error),
+    /// RPC node couldn't be reached or the connection failed.
+    #[error("rpc transport failed: {0}")]
+    Transport(#[source] TransportError),
+    /// RPC node refused the request because too many requests were sent.
+    #[error("rate limited by rpc node: {0}")]
+    RateLimited(#[source] TransportError),
+    /// RPC node returned an error response.
+    #[error("rpc call failed: {0}")]
+    RpcCallFailed(#[source] TransportError),
+    /// Contract call reverted, with the decoded revert reason when the revert data has one.
+    #[error("contract call reverted: {}", .0.as_deref().unwrap_or("no reason"))]
+    Reverted(Option<String>),
+    /// Data returned by the chain couldn't be ABI decoded.
+    #[error("abi decode failed: {0}")]
+    AbiDecode(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error("contract call failed: {0}")]
+    ContractCallFailed(#[source] alloy_contract::Error),
+}
+
+/// Classification of a [`TownsError`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Invalid user input such as a malformed stream id.
+    InvalidInput,
+    /// The requested item doesn't exist.
+    NotFound,
+    /// RPC node couldn't be reached.
+    Transport,
+    /// RPC node refused the request because of rate limiting.
+    RateLimited,
+    /// RPC node returned an error response.
+    Rpc,
+    /// Contract call reverted.
+    Reverted,
+    /// Data couldn't be decoded.
+    Decode,
+    /// Data is decoded but inconsistent, e.g. a miniblock with an invalid hash.
+    Integrity,
+}
+
+impl TownsError {
+    /// Classify the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            TownsError::InvalidArgument(_)
+            | TownsError::InvalidArgumentWithValue(_, _)
+            | TownsError::InvalidStreamIdLength(_, _, _)
+            | TownsError::UnknownStreamIdPrefix(_) => ErrorKind::InvalidInput,
+            TownsError::InvalidStreamUpdatedEvent(_, _, _) | TownsError::AbiDecode(_) => {
+                ErrorKind::Decode
+            }
+            TownsError::InvalidPreviousMiniblockHash(_, _)
+            | TownsError::InvalidPreviousMiniblockNum(_, _)
+            | TownsError::InvalidMiniblockHash(_, _)
+            | TownsError::InvalidEventHash(_, _) => ErrorKind::Integrity,
+            TownsError::NotFound => ErrorKind::NotFound,
+            TownsError::Transport(_) => ErrorKind::Transport,
+            TownsError::RateLimited(_) => ErrorKind::RateLimited,
+            TownsError::RpcCallFailed(_) | TownsError::ContractCallFailed(_) => ErrorKind::Rpc,
+            TownsError::Reverted(_) => ErrorKind::Reverted,
+        }
+    }
+
+    /// Returns true if the request that caused the error can be retried, e.g. after a
+    /// connection failure or rate limiting. Errors returned by the RPC node are retryable when
+    /// the node indicates it is temporarily unable to serve the request.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            TownsError::Transport(err)
+            | TownsError::RateLimited(err)
+            | TownsError::RpcCallFailed(err) => is_retryable_transport_error(err),
+            _ => false,
+        }
+    }
+}
+
+/// Returns true if the request that failed with the given transport error can be retried.
+pub fn is_retryable_transport_error(err: &TransportError) -> bool {
+    match err {
+        RpcError::ErrorResp(resp) => is_retryable_error_payload(resp),
+        RpcError::Transport(TransportErrorKind::HttpError(http)) => {
+            http.is_rate_limit_err() || http.is_temporarily_unavailable()
+        }
+        RpcError::Transport(TransportErrorKind::PubsubUnavailable) => false,
+        RpcError::Transport(_) | RpcError::NullResp => true,
+        _ => false,
+    }
+}
+
+/// Returns true if the request for which the RPC node returned the error response can be retried.
+pub fn is_retryable_error_payload<E>(resp: &ErrorPayload<E>) -> bool {
+    // some providers use rate limit error codes when a get_logs call returns too many results,
+    // repeating the same request won't help
+    resp.is_retry_err() && !is_log_range_error_payload(resp)
+}
+
+/// Returns true if the RPC node rejected a get_logs call because the block range is too large or
+/// it matches too many logs. The call can be repeated with a smaller block range.
+pub fn is_log_range_error(err: &TransportError) -> bool {
+    err.as_error_resp().is_some_and(is_log_range_error_payload)
+}
+
+/// Messages RPC providers use when a get_logs call covers too many blocks or results.
+const LOG_RANGE_ERRORS: [&str; 9] = [
+    "query returned more than",
+    "too many results",
+    "too many logs",
+    "block range",
+    "range too large",
+    "range is too large",
+    "response size exceeded",
+    "response size should not",
+    "exceeds max results",
+];
+
+fn is_log_range_error_payload<E>(resp: &ErrorPayload<E>) -> bool {
+    let message = resp.message.to_lowercase();
+    LOG_RANGE_ERRORS
+        .iter()
+        .any(|pattern| message.contains(pattern))
+}
+
+impl From<TransportError> for TownsError {
+    fn from(err: TransportError) -> Self {
+        if let Some(resp) = err.as_error_resp() {
+            if resp.message.contains("revert") {
+                return TownsError::Reverted(resp.as_revert_data().and_then(revert_reason));
+            }
+            if is_retryable_error_payload(resp) && is_rate_limit_code(resp.code) {
+                return TownsError::RateLimited(err);
+            }
+            return TownsError::RpcCallFailed(err);
+        }
+
+        match err.as_transport_err() {
+            Some(TransportErrorKind::HttpError(http)) if http.is_rate_limit_err() => {
+                TownsError::RateLimited(err)
+            }
+            // custom transport errors are only retryable when they carry a 429 response
+            Some(kind @ TransportErrorKind::Custom(_)) if kind.is_retry_err() => {
+                TownsError::RateLimited(err)
+            }
+            Some(_) if is_retryable_transport_error(&err) => TownsError::Transport(err),
+            Some(_) => TownsError::RpcCallFailed(err),
+            None if err.is_deser_error() => TownsError::AbiDecode(Box::new(err)),
+            None => TownsError::RpcCallFailed(err),
+        }
+    }
+}
+
+impl From<alloy_contract::Error> for TownsError {
+    fn from(err: alloy_contract::Error) -> Self {
+        match err {
+            alloy_contract::Error::TransportError(err) => TownsError::from(err),
+            alloy_contract::Error::AbiError(err) => TownsError::AbiDecode(Box::new(err)),
+            alloy_contract::Error::ZeroData(_, _) => TownsError::AbiDecode(Box::new(err)),
+            err => TownsError::ContractCallFailed(err),
+        }
+    }
+}
+
+impl From<alloy_sol_types::Error> for TownsError {
+    fn from(err: alloy_sol_types::Error) -> Self {
+        TownsError::AbiDecode(Box::new(err))
+    }
+}
+
+/// Decode the reason from revert data. The registry contracts revert with a plain reason such
+/// as `NOT_FOUND`, other revert data such as panics are described by alloy.
+fn revert_reason(data: alloy_primitives::Bytes) -> Option<String> {
+    if data.is_empty() {
+        return None;
+    }
+    match alloy_sol_types::Revert::abi_decode(&data) {
+        Ok(revert) => Some(revert.reason),
+        Err(_) => alloy_sol_types::decode_revert_reason(&data),
+    }
+}
+
+/// Error codes RPC providers use for rate limiting, other retryable error responses are
+/// temporary node failures.
+fn is_rate_limit_code(code: i64) -> bool {
+    matches!(code, 429 | -32005 | -32007 | -32012 | -32016 | 1008)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_sol_types::Revert;
+
+    fn error_resp(code: i64, message: &'static str, data: Option<&str>) -> TransportError {
+        RpcError::ErrorResp(ErrorPayload {
+            code,
+            message: message.into(),
+            data: data.map(|data| serde_json::value::to_raw_value(data).unwrap()),
+        })
+    }
+
+    #[test]
+    fn classify_transport_errors() {
+        let err = TownsError::from(TransportErrorKind::http_error(429, String::new()));
+        assert_eq!(ErrorKind::RateLimited, err.kind());
+        assert!(err.is_retryable());
+
+        let err = TownsError::from(TransportErrorKind::http_error(503, String::new()));
+        assert_eq!(ErrorKind::Transport, err.kind());
+        assert!(err.is_retryable());
+
+        let err = TownsError::from(TransportErrorKind::http_error(400, String::new()));
+        assert_eq!(ErrorKind::Rpc, err.kind());
+        assert!(!err.is_retryable());
+
+        let err = TownsError::from(TransportErrorKind::backend_gone());
+        assert_eq!(ErrorKind::Transport, err.kind());
+        assert!(err.is_retryable());
+
+        let err = TownsError::from(error_resp(-32005, "limit exceeded", None));
+        assert_eq!(ErrorKind::RateLimited, err.kind());
+        assert!(err.is_retryable());
+
+        let err = TownsError::from(error_resp(-32000, "header not found", None));
+        assert_eq!(ErrorKind::Rpc, err.kind());
+        assert!(err.is_retryable());
+
+        let err = TownsError::from(error_resp(-32602, "invalid params", None));
+        assert_eq!(ErrorKind::Rpc, err.kind());
+        assert!(!err.is_retryable());
+    }
+
+    #[test]
+    fn classify_log_range_errors() {
+        let err = error_resp(-32005, "query returned more than 10000 results", None);
+        assert!(is_log_range_error(&err));
+        assert!(!is_retryable_transport_error(&err));
+        assert_eq!(ErrorKind::Rpc, TownsError::from(err).kind());
+
+        let err = error_resp(-32600, "exceed maximum block range: 5000", None);
+        assert!(is_log_range_error(&err));
+
+        let err = error_resp(-32005, "limit exceeded", None);
+        assert!(!is_log_range_error(&err));
+        assert!(is_retryable_transport_error(&err));
+    }
+
+    #[test]
+    fn decode_revert_reason() {
+        let data = alloy_primitives::hex::encode_prefixed(Revert::from("NOT_FOUND").abi_encode());
+        let err = TownsError::from(error_resp(3, "execution reverted", Some(&data)));
+        assert!(matches!(&err, TownsError::Reverted(Some(reason)) if reason == "NOT_FOUND"));
+        assert_eq!(ErrorKind::Reverted, err.kind());
+        assert!(!err.is_retryable());
+
+        let err = TownsError::from(error_resp(3, "execution reverted", Some("0x")));
+        assert!(matches!(err, TownsError::Reverted(None)));
+    }
+
+    #[test]
+    fn classify_input_errors() {
+        assert_eq!(
+            ErrorKind::InvalidInput,
+            TownsError::UnknownStreamIdPrefix(0x12).kind()
+        );
+        assert_eq!(ErrorKind::NotFound, TownsError::NotFound.kind());
+        assert_eq!(
+            ErrorKind::Integrity,
+            TownsError::InvalidPreviousMiniblockNum(1, 2).kind()
+        );
+        assert!(!TownsError::NotFound.is_retryable());
+    }
 }
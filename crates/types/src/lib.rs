@@ -1,5 +1,6 @@
 //! towns protocol core types
 mod errors;
+pub mod protocol;
 mod stream_id;
 
 pub use errors::*;
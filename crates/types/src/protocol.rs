@@ -0,0 +1,196 @@
+//! Subset of the river protocol messages that are exchanged with stream nodes.
+//!
+//! Only the fields that are required to fetch and inspect miniblocks are declared. Payloads that
+//! are not interpreted are kept as their raw encoded bytes.
+
+use alloy_primitives::{FixedBytes, Keccak256};
+
+const HASH_HEADER: &[u8] = b"CSBLANCA";
+const HASH_SEPARATOR: &[u8] = b"ABCDEFG>";
+const HASH_FOOTER: &[u8] = b"<GFEDCBA";
+
+/// Calculate the hash over an encoded event as used by the river protocol.
+pub fn river_hash(data: &[u8]) -> FixedBytes<32> {
+    let mut hasher = Keccak256::new();
+    hasher.update(HASH_HEADER);
+    hasher.update((data.len() as u64).to_le_bytes());
+    hasher.update(HASH_SEPARATOR);
+    hasher.update(data);
+    hasher.update(HASH_FOOTER);
+    hasher.finalize()
+}
+
+/// Envelope wraps an encoded event together with its hash and the creator signature.
+#[derive(Clone, PartialEq, Eq, ::prost::Message)]
+pub struct Envelope {
+    #[prost(bytes = "vec", tag = "1")]
+    pub hash: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub signature: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub event: Vec<u8>,
+}
+
+/// Miniblock as stored and served by stream nodes.
+#[derive(Clone, PartialEq, Eq, ::prost::Message)]
+pub struct Miniblock {
+    #[prost(message, repeated, tag = "1")]
+    pub events: Vec<Envelope>,
+    #[prost(message, optional, tag = "2")]
+    pub header: Option<Envelope>,
+}
+
+/// Timestamp is wire compatible with `google.protobuf.Timestamp`.
+#[derive(Clone, Copy, PartialEq, Eq, ::prost::Message)]
+pub struct Timestamp {
+    #[prost(int64, tag = "1")]
+    pub seconds: i64,
+    #[prost(int32, tag = "2")]
+    pub nanos: i32,
+}
+
+#[derive(Clone, PartialEq, Eq, ::prost::Message)]
+pub struct StreamEvent {
+    #[prost(bytes = "vec", tag = "1")]
+    pub creator_address: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub salt: Vec<u8>,
+    #[prost(bytes = "vec", optional, tag = "3")]
+    pub prev_miniblock_hash: Option<Vec<u8>>,
+    #[prost(int64, tag = "4")]
+    pub created_at_epoch_ms: i64,
+    #[prost(
+        oneof = "stream_event::Payload",
+        tags = "100, 101, 102, 103, 104, 105, 106, 107, 108, 109, 110"
+    )]
+    pub payload: Option<stream_event::Payload>,
+}
+
+pub mod stream_event {
+    /// Event payload, only the miniblock header is decoded.
+    #[derive(Clone, PartialEq, Eq, ::prost::Oneof)]
+    pub enum Payload {
+        #[prost(message, tag = "100")]
+        MiniblockHeader(super::MiniblockHeader),
+        #[prost(bytes, tag = "101")]
+        MemberPayload(Vec<u8>),
+        #[prost(bytes, tag = "102")]
+        SpacePayload(Vec<u8>),
+        #[prost(bytes, tag = "103")]
+        ChannelPayload(Vec<u8>),
+        #[prost(bytes, tag = "104")]
+        UserPayload(Vec<u8>),
+        #[prost(bytes, tag = "105")]
+        UserSettingsPayload(Vec<u8>),
+        #[prost(bytes, tag = "106")]
+        UserMetadataPayload(Vec<u8>),
+        #[prost(bytes, tag = "107")]
+        UserInboxPayload(Vec<u8>),
+        #[prost(bytes, tag = "108")]
+        MediaPayload(Vec<u8>),
+        #[prost(bytes, tag = "109")]
+        DmChannelPayload(Vec<u8>),
+        #[prost(bytes, tag = "110")]
+        GdmChannelPayload(Vec<u8>),
+    }
+
+    impl Payload {
+        pub fn name(&self) -> &'static str {
+            match self {
+                Payload::MiniblockHeader(_) => "miniblock_header",
+                Payload::MemberPayload(_) => "member",
+                Payload::SpacePayload(_) => "space",
+                Payload::ChannelPayload(_) => "channel",
+                Payload::UserPayload(_) => "user",
+                Payload::UserSettingsPayload(_) => "user_settings",
+                Payload::UserMetadataPayload(_) => "user_metadata",
+                Payload::UserInboxPayload(_) => "user_inbox",
+                Payload::MediaPayload(_) => "media",
+                Payload::DmChannelPayload(_) => "dm_channel",
+                Payload::GdmChannelPayload(_) => "gdm_channel",
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, ::prost::Message)]
+pub struct MiniblockHeader {
+    #[prost(int64, tag = "1")]
+    pub miniblock_num: i64,
+    #[prost(bytes = "vec", tag = "2")]
+    pub prev_miniblock_hash: Vec<u8>,
+    #[prost(message, optional, tag = "3")]
+    pub timestamp: Option<Timestamp>,
+    #[prost(bytes = "vec", repeated, tag = "4")]
+    pub event_hashes: Vec<Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "5")]
+    pub snapshot: Option<Vec<u8>>,
+    #[prost(int64, tag = "6")]
+    pub event_num_offset: i64,
+    #[prost(int64, tag = "7")]
+    pub prev_snapshot_miniblock_num: i64,
+    #[prost(bytes = "vec", optional, tag = "8")]
+    pub snapshot_hash: Option<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Eq, ::prost::Message)]
+pub struct GetMiniblocksRequest {
+    #[prost(bytes = "vec", tag = "1")]
+    pub stream_id: Vec<u8>,
+    #[prost(int64, tag = "2")]
+    pub from_inclusive: i64,
+    #[prost(int64, tag = "3")]
+    pub to_exclusive: i64,
+    #[prost(bool, tag = "4")]
+    pub omit_snapshots: bool,
+}
+
+#[derive(Clone, PartialEq, Eq, ::prost::Message)]
+pub struct GetMiniblocksResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub miniblocks: Vec<Miniblock>,
+    #[prost(bool, tag = "2")]
+    pub terminus: bool,
+}
+
+#[derive(Clone, PartialEq, Eq, ::prost::Message)]
+pub struct GetLastMiniblockHashRequest {
+    #[prost(bytes = "vec", tag = "1")]
+    pub stream_id: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Eq, ::prost::Message)]
+pub struct GetLastMiniblockHashResponse {
+    #[prost(bytes = "vec", tag = "1")]
+    pub hash: Vec<u8>,
+    #[prost(int64, tag = "2")]
+    pub miniblock_num: i64,
+}
+
+impl Miniblock {
+    /// Decode the miniblock header from the header envelope.
+    pub fn decode_header(&self) -> Result<MiniblockHeader, crate::TownsError> {
+        let envelope = self
+            .header
+            .as_ref()
+            .ok_or(crate::TownsError::InvalidArgument("miniblock header"))?;
+        let event = envelope.decode_event()?;
+        match event.payload {
+            Some(stream_event::Payload::MiniblockHeader(header)) => Ok(header),
+            _ => Err(crate::TownsError::InvalidArgument("miniblock header")),
+        }
+    }
+}
+
+impl Envelope {
+    /// Recalculate the hash over the encoded event.
+    pub fn compute_hash(&self) -> FixedBytes<32> {
+        river_hash(&self.event)
+    }
+
+    /// Decode the stream event that is wrapped in this envelope.
+    pub fn decode_event(&self) -> Result<StreamEvent, crate::TownsError> {
+        <StreamEvent as ::prost::Message>::decode(self.event.as_slice())
+            .map_err(|e| crate::TownsError::InvalidArgumentWithValue("stream event", e.to_string()))
+    }
+}
@@ -1,9 +1,14 @@
 use crate::TownsError;
 use alloy_primitives::{Address, FixedBytes};
+use sha2::{Digest, Sha256};
 use std::fmt;
 use std::fmt::Formatter;
+#[cfg(feature = "serde")]
+use std::str::FromStr;
 
 pub const STREAM_ID_LEN: usize = 32;
+/// Length of the canonical form of user streams, the prefix followed by the user address.
+pub const SHORT_STREAM_ID_LEN: usize = 21;
 
 pub const SPACE_STREAM_ID_PREFIX: u8 = 0x10;
 pub const CHANNEL_STREAM_ID_PREFIX: u8 = 0x20;
@@ -15,7 +20,34 @@ pub const USER_STREAM_ID_PREFIX: u8 = 0xa8;
 pub const USER_METADATA_STREAM_ID_PREFIX: u8 = 0xad;
 pub const MEDIA_STREAM_ID_PREFIX: u8 = 0xff;
 
+/// All known stream id prefixes.
+pub const STREAM_ID_PREFIXES: [u8; 9] = [
+    SPACE_STREAM_ID_PREFIX,
+    CHANNEL_STREAM_ID_PREFIX,
+    GDM_CHANNEL_STREAM_ID_PREFIX,
+    DM_CHANNEL_STREAM_ID_PREFIX,
+    USER_INBOX_STREAM_ID_PREFIX,
+    USER_SETTINGS_STREAM_ID_PREFIX,
+    USER_STREAM_ID_PREFIX,
+    USER_METADATA_STREAM_ID_PREFIX,
+    MEDIA_STREAM_ID_PREFIX,
+];
+
 /// StreamId uniquely identifies a stream.
+///
+/// User streams have a 21 byte canonical form, the prefix followed by the user address. All
+/// other streams are 32 bytes. The registry stores all stream ids as 32 bytes, user stream ids
+/// are padded with zeros. Both forms are accepted when parsing, formatting always uses the
+/// canonical form.
+///
+/// Stream ids are ordered by their 32 byte form. With the `serde` feature stream ids serialize
+/// as 0x-hex string in human readable formats and as canonical bytes in binary formats. With
+/// the `borsh` feature stream ids are written in compact form, see
+/// [`StreamId::decode_compact`]. Deserializing 32 byte ids never fails on the prefix, ids that
+/// can't be parsed are returned as [`StreamId::Unknown`].
+///
+/// [`StreamId::Unknown`] ids always use all 32 bytes, also when the prefix is a user stream
+/// prefix, so they survive formatting and serialization unchanged.
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum StreamId {
     /// StreamId for a stream that contains user meta data
@@ -36,6 +68,9 @@ pub enum StreamId {
     GdmChannel(FixedBytes<STREAM_ID_LEN>),
     /// StreamId for a stream that contains events for a space
     Space(FixedBytes<STREAM_ID_LEN>),
+    /// StreamId as found in registry data that can't be parsed, e.g. because of an unknown
+    /// prefix or a user stream id with non-zero padding. The prefix is the first byte.
+    Unknown(FixedBytes<STREAM_ID_LEN>),
 }
 
 impl StreamId {
@@ -50,39 +85,98 @@ impl StreamId {
             StreamId::DmChannel(_) => DM_CHANNEL_STREAM_ID_PREFIX,
             StreamId::GdmChannel(_) => GDM_CHANNEL_STREAM_ID_PREFIX,
             StreamId::Space(_) => SPACE_STREAM_ID_PREFIX,
+            StreamId::Unknown(raw) => raw[0],
         }
     }
+
+    /// Returns the stream id in the 32 byte form as used by the registry.
     pub fn as_fixed_bytes32(&self) -> FixedBytes<STREAM_ID_LEN> {
+        *self.raw()
+    }
+
+    fn raw(&self) -> &FixedBytes<STREAM_ID_LEN> {
         match self {
-            StreamId::UserMetaDataKey(raw) => raw.clone(),
-            StreamId::UserInbox(raw) => raw.clone(),
-            StreamId::User(raw) => raw.clone(),
-            StreamId::UserSettings(raw) => raw.clone(),
-            StreamId::Media(raw) => raw.clone(),
-            StreamId::Channel(raw) => raw.clone(),
-            StreamId::DmChannel(raw) => raw.clone(),
-            StreamId::GdmChannel(raw) => raw.clone(),
-            StreamId::Space(raw) => raw.clone(),
+            StreamId::UserMetaDataKey(raw)
+            | StreamId::UserInbox(raw)
+            | StreamId::User(raw)
+            | StreamId::UserSettings(raw)
+            | StreamId::Media(raw)
+            | StreamId::Channel(raw)
+            | StreamId::DmChannel(raw)
+            | StreamId::GdmChannel(raw)
+            | StreamId::Space(raw)
+            | StreamId::Unknown(raw) => raw,
         }
     }
 
-    pub fn try_from_short(from: &[u8]) -> Result<Self, TownsError> {
-        if from.len() != 21 {
-            return Err(TownsError::InvalidArgument("stream_id"));
+    /// Returns the stream id in its canonical form as used by stream nodes. User streams are 21
+    /// bytes long, all other streams 32 bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        if self.is_short() {
+            &self.raw()[..SHORT_STREAM_ID_LEN]
+        } else {
+            self.raw().as_slice()
         }
+    }
 
-        let mut id = FixedBytes::<STREAM_ID_LEN>::new([0; STREAM_ID_LEN]);
-        id[0..=20].copy_from_slice(from);
+    /// Returns true for stream ids with a 21 byte canonical form.
+    fn is_short(&self) -> bool {
+        matches!(
+            self,
+            StreamId::UserMetaDataKey(_)
+                | StreamId::UserInbox(_)
+                | StreamId::User(_)
+                | StreamId::UserSettings(_)
+        )
+    }
 
-        match from[0] {
+    /// Returns true if the compact form needs [`COMPACT_UNKNOWN_MARKER`] in front, the length
+    /// of the id doesn't follow from its prefix.
+    fn needs_compact_marker(&self) -> bool {
+        match self {
+            StreamId::Unknown(raw) => is_short_prefix(raw[0]) || raw[0] == COMPACT_UNKNOWN_MARKER,
+            _ => false,
+        }
+    }
+
+    fn from_prefix(id: FixedBytes<STREAM_ID_LEN>) -> Result<Self, TownsError> {
+        match id[0] {
             USER_METADATA_STREAM_ID_PREFIX => Ok(StreamId::UserMetaDataKey(id)),
             USER_INBOX_STREAM_ID_PREFIX => Ok(StreamId::UserInbox(id)),
             USER_STREAM_ID_PREFIX => Ok(StreamId::User(id)),
             USER_SETTINGS_STREAM_ID_PREFIX => Ok(StreamId::UserSettings(id)),
-            _ => Err(TownsError::InvalidArgument("stream id")),
+            MEDIA_STREAM_ID_PREFIX => Ok(StreamId::Media(id)),
+            CHANNEL_STREAM_ID_PREFIX => Ok(StreamId::Channel(id)),
+            DM_CHANNEL_STREAM_ID_PREFIX => Ok(StreamId::DmChannel(id)),
+            GDM_CHANNEL_STREAM_ID_PREFIX => Ok(StreamId::GdmChannel(id)),
+            SPACE_STREAM_ID_PREFIX => Ok(StreamId::Space(id)),
+            prefix => Err(TownsError::UnknownStreamIdPrefix(prefix)),
+        }
+    }
+
+    /// Parse a 21 byte user stream id.
+    pub fn try_from_short(from: &[u8]) -> Result<Self, TownsError> {
+        if from.len() != SHORT_STREAM_ID_LEN {
+            return Err(TownsError::InvalidArgument("stream_id"));
+        }
+
+        if !is_short_prefix(from[0]) {
+            if STREAM_ID_PREFIXES.contains(&from[0]) {
+                return Err(TownsError::InvalidStreamIdLength(
+                    from[0],
+                    STREAM_ID_LEN,
+                    from.len(),
+                ));
+            }
+            return Err(TownsError::UnknownStreamIdPrefix(from[0]));
         }
+
+        let mut id = FixedBytes::<STREAM_ID_LEN>::ZERO;
+        id[..SHORT_STREAM_ID_LEN].copy_from_slice(from);
+        StreamId::from_prefix(id)
     }
 
+    /// Parse a 32 byte stream id. User stream ids must be padded with zeros.
     pub fn try_from_long(from: &[u8]) -> Result<Self, TownsError> {
         if from.len() != STREAM_ID_LEN {
             return Err(TownsError::InvalidArgumentWithValue(
@@ -91,54 +185,294 @@ impl StreamId {
             ));
         }
 
-        let id = FixedBytes::<STREAM_ID_LEN>::from_slice(from);
-
-        match from[0] {
-            MEDIA_STREAM_ID_PREFIX => Ok(StreamId::Media(id)),
-            CHANNEL_STREAM_ID_PREFIX => Ok(StreamId::Channel(id)),
-            DM_CHANNEL_STREAM_ID_PREFIX => Ok(StreamId::DmChannel(id)),
-            GDM_CHANNEL_STREAM_ID_PREFIX => Ok(StreamId::GdmChannel(id)),
-            SPACE_STREAM_ID_PREFIX => Ok(StreamId::Space(id)),
-            USER_INBOX_STREAM_ID_PREFIX => Ok(StreamId::Space(id)),
-            USER_SETTINGS_STREAM_ID_PREFIX => Ok(StreamId::Space(id)),
-            USER_STREAM_ID_PREFIX => Ok(StreamId::Space(id)),
-            USER_METADATA_STREAM_ID_PREFIX => Ok(StreamId::Space(id)),
-            _ => Err(TownsError::InvalidArgumentWithValue(
-                "stream_id",
+        if is_short_prefix(from[0]) && from[SHORT_STREAM_ID_LEN..].iter().any(|b| *b != 0) {
+            return Err(TownsError::InvalidArgumentWithValue(
+                "user stream_id padding",
                 hex::encode(from),
-            )),
+            ));
+        }
+
+        StreamId::from_prefix(FixedBytes::from_slice(from))
+    }
+
+    /// Kind of stream.
+    pub fn kind(&self) -> StreamKind {
+        match self {
+            StreamId::UserMetaDataKey(_) => StreamKind::UserMetadata,
+            StreamId::UserInbox(_) => StreamKind::UserInbox,
+            StreamId::User(_) => StreamKind::User,
+            StreamId::UserSettings(_) => StreamKind::UserSettings,
+            StreamId::Media(_) => StreamKind::Media,
+            StreamId::Channel(_) => StreamKind::Channel,
+            StreamId::DmChannel(_) => StreamKind::Dm,
+            StreamId::GdmChannel(_) => StreamKind::Gdm,
+            StreamId::Space(_) => StreamKind::Space,
+            StreamId::Unknown(raw) => StreamKind::Unknown(raw[0]),
+        }
+    }
+
+    /// Address of the user that owns the stream, `None` for streams that are not user streams.
+    pub fn user_address(&self) -> Option<Address> {
+        self.kind()
+            .is_user()
+            .then(|| Address::from_slice(&self.raw()[1..SHORT_STREAM_ID_LEN]))
+    }
+
+    /// Address of the space contract for space and channel streams, `None` for other streams.
+    pub fn space_address(&self) -> Option<Address> {
+        matches!(self.kind(), StreamKind::Space | StreamKind::Channel)
+            .then(|| Address::from_slice(&self.raw()[1..SHORT_STREAM_ID_LEN]))
+    }
+
+    /// Space stream for the space contract.
+    pub fn space(space_address: &Address) -> StreamId {
+        StreamId::Space(Self::from_addr(SPACE_STREAM_ID_PREFIX, space_address))
+    }
+
+    /// Default channel of the space, the channel that is created together with the space.
+    pub fn default_channel(space_address: &Address) -> StreamId {
+        Self::channel(space_address, [0; 11])
+    }
+
+    /// Channel in the space, `suffix` distinguishes channels within the space and is randomly
+    /// generated when the channel is created.
+    pub fn channel(space_address: &Address, suffix: [u8; 11]) -> StreamId {
+        let mut id = Self::from_addr(CHANNEL_STREAM_ID_PREFIX, space_address);
+        id[SHORT_STREAM_ID_LEN..].copy_from_slice(&suffix);
+        StreamId::Channel(id)
+    }
+
+    /// DM stream between two users. The id is derived from the sha256 hash of the lowercase
+    /// 0x-hex addresses of both users, sorted and joined with `-`, as in the protocol
+    /// `DMStreamIdForUsers`. The result is independent of the order of the participants.
+    pub fn dm(a: &Address, b: &Address) -> StreamId {
+        let mut participants = [a, b].map(|addr| format!("0x{}", hex::encode(addr)));
+        participants.sort();
+
+        let hash = Sha256::digest(participants.join("-"));
+        let mut id = FixedBytes::<STREAM_ID_LEN>::ZERO;
+        id[0] = DM_CHANNEL_STREAM_ID_PREFIX;
+        id[1..].copy_from_slice(&hash[..STREAM_ID_LEN - 1]);
+        StreamId::DmChannel(id)
+    }
+
+    /// GDM stream, `id` is randomly generated when the GDM is created.
+    pub fn gdm(id: [u8; STREAM_ID_LEN - 1]) -> StreamId {
+        StreamId::GdmChannel(Self::from_suffix(GDM_CHANNEL_STREAM_ID_PREFIX, &id))
+    }
+
+    /// Media stream, `id` is randomly generated when the media stream is created.
+    pub fn media(id: [u8; STREAM_ID_LEN - 1]) -> StreamId {
+        StreamId::Media(Self::from_suffix(MEDIA_STREAM_ID_PREFIX, &id))
+    }
+
+    fn from_suffix(prefix: u8, suffix: &[u8; STREAM_ID_LEN - 1]) -> FixedBytes<STREAM_ID_LEN> {
+        let mut id = FixedBytes::<STREAM_ID_LEN>::ZERO;
+        id[0] = prefix;
+        id[1..].copy_from_slice(suffix);
+        id
+    }
+
+    /// Stream id from registry data. Ids that can't be parsed, e.g. because of an unknown
+    /// prefix, are returned as [`StreamId::Unknown`] instead of failing.
+    pub fn from_registry(raw: &FixedBytes<STREAM_ID_LEN>) -> StreamId {
+        StreamId::try_from(raw).unwrap_or(StreamId::Unknown(*raw))
+    }
+
+    /// Returns true if the stream id has an unknown prefix.
+    pub fn is_unknown(&self) -> bool {
+        matches!(self, StreamId::Unknown(_))
+    }
+
+    /// Write the stream id in compact form: the canonical form, where the length follows from
+    /// the prefix. [`StreamId::Unknown`] ids whose length doesn't follow from the prefix are
+    /// written as [`COMPACT_UNKNOWN_MARKER`] followed by all 32 bytes.
+    pub fn encode_compact(&self, out: &mut Vec<u8>) {
+        if self.needs_compact_marker() {
+            out.push(COMPACT_UNKNOWN_MARKER);
+        }
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    /// Decode a stream id in compact form from the start of `buf` and return it together with
+    /// the number of bytes read, see [`StreamId::encode_compact`]. Ids that can't be parsed are
+    /// returned as [`StreamId::Unknown`].
+    pub fn decode_compact(buf: &[u8]) -> Result<(StreamId, usize), TownsError> {
+        let Some(prefix) = buf.first() else {
+            return Err(TownsError::InvalidArgument("stream_id"));
+        };
+        if *prefix == COMPACT_UNKNOWN_MARKER {
+            let len = STREAM_ID_LEN + 1;
+            if buf.len() < len {
+                return Err(TownsError::InvalidStreamIdLength(*prefix, len, buf.len()));
+            }
+            let raw = FixedBytes::from_slice(&buf[1..len]);
+            return Ok((StreamId::Unknown(raw), len));
+        }
+
+        let len = if is_short_prefix(*prefix) {
+            SHORT_STREAM_ID_LEN
+        } else {
+            STREAM_ID_LEN
+        };
+        if buf.len() < len {
+            return Err(TownsError::InvalidStreamIdLength(*prefix, len, buf.len()));
         }
+        if len == STREAM_ID_LEN {
+            let raw = FixedBytes::from_slice(&buf[..len]);
+            return Ok((StreamId::from_registry(&raw), len));
+        }
+        Ok((StreamId::try_from(&buf[..len])?, len))
+    }
+
+    fn from_addr(prefix: u8, addr: &Address) -> FixedBytes<STREAM_ID_LEN> {
+        let mut id = FixedBytes::<STREAM_ID_LEN>::ZERO;
+        id[0] = prefix;
+        id[1..SHORT_STREAM_ID_LEN].copy_from_slice(addr.as_slice());
+        id
     }
 
     pub fn user_stream_from_addr(addr: &Address) -> StreamId {
-        let mut id = FixedBytes::<STREAM_ID_LEN>::new([0u8; STREAM_ID_LEN]);
-        id[0] = USER_STREAM_ID_PREFIX;
-        id[1..=20].copy_from_slice(addr.as_slice());
-        StreamId::User(id)
+        StreamId::User(Self::from_addr(USER_STREAM_ID_PREFIX, addr))
     }
 
     pub fn user_settings_stream_from_addr(addr: &Address) -> StreamId {
-        let mut id = FixedBytes::<STREAM_ID_LEN>::new([0u8; STREAM_ID_LEN]);
-        id[0] = USER_SETTINGS_STREAM_ID_PREFIX;
-        id[1..=20].copy_from_slice(addr.as_slice());
-        StreamId::User(id)
+        StreamId::UserSettings(Self::from_addr(USER_SETTINGS_STREAM_ID_PREFIX, addr))
     }
 
     pub fn user_inbox_stream_from_addr(addr: &Address) -> StreamId {
-        let mut id = FixedBytes::<STREAM_ID_LEN>::new([0u8; STREAM_ID_LEN]);
-        id[0] = USER_INBOX_STREAM_ID_PREFIX;
-        id[1..=20].copy_from_slice(addr.as_slice());
-        StreamId::User(id)
+        StreamId::UserInbox(Self::from_addr(USER_INBOX_STREAM_ID_PREFIX, addr))
     }
 
     pub fn user_metadata_key_stream_from_addr(addr: &Address) -> StreamId {
-        let mut id = FixedBytes::<STREAM_ID_LEN>::new([0u8; STREAM_ID_LEN]);
-        id[0] = USER_METADATA_STREAM_ID_PREFIX;
-        id[1..=20].copy_from_slice(addr.as_slice());
-        StreamId::User(id)
+        StreamId::UserMetaDataKey(Self::from_addr(USER_METADATA_STREAM_ID_PREFIX, addr))
     }
 }
 
+/// Kind of stream, determined by the stream id prefix.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub enum StreamKind {
+    Space,
+    Channel,
+    Dm,
+    Gdm,
+    Media,
+    User,
+    UserInbox,
+    UserSettings,
+    UserMetadata,
+    /// Stream with an unknown prefix.
+    Unknown(u8),
+}
+
+impl StreamKind {
+    /// All known stream kinds.
+    pub const ALL: [StreamKind; 9] = [
+        StreamKind::Space,
+        StreamKind::Channel,
+        StreamKind::Dm,
+        StreamKind::Gdm,
+        StreamKind::Media,
+        StreamKind::User,
+        StreamKind::UserInbox,
+        StreamKind::UserSettings,
+        StreamKind::UserMetadata,
+    ];
+
+    /// Stream id prefix for streams of this kind.
+    pub fn prefix(&self) -> u8 {
+        match self {
+            StreamKind::Space => SPACE_STREAM_ID_PREFIX,
+            StreamKind::Channel => CHANNEL_STREAM_ID_PREFIX,
+            StreamKind::Dm => DM_CHANNEL_STREAM_ID_PREFIX,
+            StreamKind::Gdm => GDM_CHANNEL_STREAM_ID_PREFIX,
+            StreamKind::Media => MEDIA_STREAM_ID_PREFIX,
+            StreamKind::User => USER_STREAM_ID_PREFIX,
+            StreamKind::UserInbox => USER_INBOX_STREAM_ID_PREFIX,
+            StreamKind::UserSettings => USER_SETTINGS_STREAM_ID_PREFIX,
+            StreamKind::UserMetadata => USER_METADATA_STREAM_ID_PREFIX,
+            StreamKind::Unknown(prefix) => *prefix,
+        }
+    }
+
+    /// Kind of streams with the given prefix, `None` for unknown prefixes.
+    pub fn from_prefix(prefix: u8) -> Option<StreamKind> {
+        StreamKind::ALL
+            .into_iter()
+            .find(|kind| kind.prefix() == prefix)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            StreamKind::Space => "space",
+            StreamKind::Channel => "channel",
+            StreamKind::Dm => "dm",
+            StreamKind::Gdm => "gdm",
+            StreamKind::Media => "media",
+            StreamKind::User => "user",
+            StreamKind::UserInbox => "user-inbox",
+            StreamKind::UserSettings => "user-settings",
+            StreamKind::UserMetadata => "user-metadata",
+            StreamKind::Unknown(_) => "unknown",
+        }
+    }
+
+    /// Returns true for streams that belong to a user, false for unknown streams even if the
+    /// prefix is a user stream prefix.
+    pub fn is_user(&self) -> bool {
+        matches!(
+            self,
+            StreamKind::User
+                | StreamKind::UserInbox
+                | StreamKind::UserSettings
+                | StreamKind::UserMetadata
+        )
+    }
+}
+
+impl fmt::Display for StreamKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamKind::Unknown(prefix) => write!(f, "unknown({:#04x})", prefix),
+            kind => f.write_str(kind.name()),
+        }
+    }
+}
+
+/// Parses the kind name, or the prefix as decimal or 0x-hex number.
+impl std::str::FromStr for StreamKind {
+    type Err = TownsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(kind) = StreamKind::ALL.into_iter().find(|kind| kind.name() == s) {
+            return Ok(kind);
+        }
+
+        let prefix = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Some(hex_encoded) => u8::from_str_radix(hex_encoded, 16),
+            None => s.parse(),
+        }
+        .map_err(|_| TownsError::InvalidArgumentWithValue("stream kind", s.to_string()))?;
+
+        StreamKind::from_prefix(prefix).ok_or(TownsError::UnknownStreamIdPrefix(prefix))
+    }
+}
+
+/// Marks [`StreamId::Unknown`] ids in compact form whose length doesn't follow from the prefix.
+/// It isn't a stream id prefix.
+pub const COMPACT_UNKNOWN_MARKER: u8 = 0x00;
+
+/// Returns true for prefixes of streams that have a 21 byte canonical form.
+fn is_short_prefix(prefix: u8) -> bool {
+    matches!(
+        prefix,
+        USER_METADATA_STREAM_ID_PREFIX
+            | USER_INBOX_STREAM_ID_PREFIX
+            | USER_STREAM_ID_PREFIX
+            | USER_SETTINGS_STREAM_ID_PREFIX
+    )
+}
+
 impl std::str::FromStr for StreamId {
     type Err = TownsError;
 
@@ -152,96 +486,147 @@ impl TryFrom<&[u8]> for StreamId {
 
     fn try_from(from: &[u8]) -> Result<Self, Self::Error> {
         match from.len() {
-            21 => StreamId::try_from_short(from),
-            32 => StreamId::try_from_long(from),
+            SHORT_STREAM_ID_LEN => StreamId::try_from_short(from),
+            STREAM_ID_LEN => StreamId::try_from_long(from),
             _ => Err(TownsError::InvalidArgument("stream_id")),
         }
     }
 }
 
-impl From<&alloy_primitives::FixedBytes<32>> for StreamId {
-    fn from(from: &alloy_primitives::FixedBytes<32>) -> Self {
-        StreamId::try_from(from.as_slice()).unwrap()
+impl TryFrom<&FixedBytes<STREAM_ID_LEN>> for StreamId {
+    type Error = TownsError;
+
+    fn try_from(from: &FixedBytes<STREAM_ID_LEN>) -> Result<Self, Self::Error> {
+        StreamId::try_from_long(from.as_slice())
     }
 }
 
 impl TryFrom<&str> for StreamId {
     type Error = TownsError;
 
-    fn try_from(mut from: &str) -> Result<Self, Self::Error> {
-        if from.len() > 2 && from.starts_with("0x") || from.starts_with("0X") {
-            from = &from[2..];
-        }
+    fn try_from(from: &str) -> Result<Self, Self::Error> {
+        let hex_encoded = from
+            .strip_prefix("0x")
+            .or_else(|| from.strip_prefix("0X"))
+            .unwrap_or(from);
 
-        let raw = hex::decode(from)
+        let raw = hex::decode(hex_encoded)
             .map_err(|op| TownsError::InvalidArgumentWithValue("stream_id", op.to_string()))?;
 
-        Ok(StreamId::try_from(raw.as_slice())
-            .map_err(|_| TownsError::InvalidArgumentWithValue("stream_id", hex::encode(from)))?)
+        StreamId::try_from(raw.as_slice())
     }
 }
 
-impl Into<Vec<u8>> for StreamId {
-    fn into(self) -> Vec<u8> {
-        match self {
-            StreamId::UserMetaDataKey(raw) => {
-                let mut result = raw.to_vec();
-                result.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
-                result
-            }
-            StreamId::UserInbox(raw) => {
-                let mut result = raw.to_vec();
-                result.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
-                result
+impl From<StreamId> for Vec<u8> {
+    fn from(id: StreamId) -> Self {
+        id.as_bytes().to_vec()
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", hex::encode(self.as_bytes()))
+    }
+}
+
+impl PartialOrd for StreamId {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for StreamId {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.raw().cmp(other.raw())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for StreamId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_bytes(self.as_bytes())
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for StreamId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StreamIdVisitor;
+
+        impl serde::de::Visitor<'_> for StreamIdVisitor {
+            type Value = StreamId;
+
+            fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                f.write_str("a stream id as hex string or bytes")
             }
-            StreamId::User(raw) => {
-                let mut result = raw.to_vec();
-                result.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
-                result
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                let hex_encoded = v
+                    .strip_prefix("0x")
+                    .or_else(|| v.strip_prefix("0X"))
+                    .unwrap_or(v);
+                match FixedBytes::<STREAM_ID_LEN>::from_str(hex_encoded) {
+                    Ok(raw) => Ok(StreamId::from_registry(&raw)),
+                    Err(_) => StreamId::try_from(v).map_err(E::custom),
+                }
             }
-            StreamId::UserSettings(raw) => {
-                let mut result = raw.to_vec();
-                result.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
-                result
+
+            fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                match <&[u8; STREAM_ID_LEN]>::try_from(v) {
+                    Ok(raw) => Ok(StreamId::from_registry(&FixedBytes::from(raw))),
+                    Err(_) => StreamId::try_from(v).map_err(E::custom),
+                }
             }
-            StreamId::Media(raw) => raw.to_vec(),
-            StreamId::Channel(raw) => raw.to_vec(),
-            StreamId::DmChannel(raw) => raw.to_vec(),
-            StreamId::GdmChannel(raw) => raw.to_vec(),
-            StreamId::Space(raw) => raw.to_vec(),
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(StreamIdVisitor)
+        } else {
+            deserializer.deserialize_bytes(StreamIdVisitor)
         }
     }
 }
 
-impl fmt::Display for StreamId {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        match self {
-            StreamId::UserMetaDataKey(raw) => write!(f, "0x{}", hex::encode(raw)),
-            StreamId::UserInbox(raw) => write!(f, "0x{}", hex::encode(raw)),
-            StreamId::User(raw) => write!(f, "0x{}", hex::encode(raw)),
-            StreamId::UserSettings(raw) => write!(f, "0x{}", hex::encode(raw)),
-            StreamId::Media(raw) => write!(f, "0x{}", hex::encode(raw)),
-            StreamId::Channel(raw) => write!(f, "0x{}", hex::encode(raw)),
-            StreamId::DmChannel(raw) => write!(f, "0x{}", hex::encode(raw)),
-            StreamId::GdmChannel(raw) => write!(f, "0x{}", hex::encode(raw)),
-            StreamId::Space(raw) => write!(f, "0x{}", hex::encode(raw)),
+#[cfg(feature = "borsh")]
+impl borsh::BorshSerialize for StreamId {
+    fn serialize<W: borsh::io::Write>(&self, writer: &mut W) -> borsh::io::Result<()> {
+        let mut buf = Vec::with_capacity(STREAM_ID_LEN + 1);
+        self.encode_compact(&mut buf);
+        writer.write_all(&buf)
+    }
+}
+
+#[cfg(feature = "borsh")]
+impl borsh::BorshDeserialize for StreamId {
+    fn deserialize_reader<R: borsh::io::Read>(reader: &mut R) -> borsh::io::Result<Self> {
+        let mut buf = [0u8; STREAM_ID_LEN];
+        reader.read_exact(&mut buf[..1])?;
+        if buf[0] == COMPACT_UNKNOWN_MARKER {
+            reader.read_exact(&mut buf)?;
+            return Ok(StreamId::Unknown(FixedBytes::from(buf)));
+        }
+        let len = if is_short_prefix(buf[0]) {
+            SHORT_STREAM_ID_LEN
+        } else {
+            STREAM_ID_LEN
+        };
+        reader.read_exact(&mut buf[1..len])?;
+        if len == STREAM_ID_LEN {
+            return Ok(StreamId::from_registry(&FixedBytes::from(buf)));
         }
+        StreamId::try_from(&buf[..len]).map_err(|err| {
+            borsh::io::Error::new(borsh::io::ErrorKind::InvalidData, err.to_string())
+        })
     }
 }
 
 impl AsRef<FixedBytes<STREAM_ID_LEN>> for StreamId {
     fn as_ref(&self) -> &FixedBytes<STREAM_ID_LEN> {
-        match self {
-            StreamId::UserMetaDataKey(raw) => raw,
-            StreamId::UserInbox(raw) => raw,
-            StreamId::User(raw) => raw,
-            StreamId::UserSettings(raw) => raw,
-            StreamId::Media(raw) => raw,
-            StreamId::Channel(raw) => raw,
-            StreamId::DmChannel(raw) => raw,
-            StreamId::GdmChannel(raw) => raw,
-            StreamId::Space(raw) => raw,
-        }
+        self.raw()
     }
 }
 
@@ -253,7 +638,7 @@ mod tests {
     fn parse_empty_stream_id() {
         let e =
             StreamId::try_from(Vec::from([SPACE_STREAM_ID_PREFIX, 0x2]).as_slice()).unwrap_err();
-        if !matches!(TownsError::InvalidArgument("stream_id"), e) {
+        if !matches!(e, TownsError::InvalidArgument("stream_id")) {
             panic!("expected InvalidArgument error");
         }
     }
@@ -262,7 +647,7 @@ mod tests {
     fn parse_too_short_stream_id() {
         let e =
             StreamId::try_from(Vec::from([SPACE_STREAM_ID_PREFIX, 0x2]).as_slice()).unwrap_err();
-        if !matches!(TownsError::InvalidArgument("stream_id"), e) {
+        if !matches!(e, TownsError::InvalidArgument("stream_id")) {
             panic!("expected InvalidArgument error");
         }
     }
@@ -288,4 +673,287 @@ mod tests {
         exp[STREAM_ID_LEN - 1] = 0x09;
         assert_eq!(StreamId::GdmChannel(exp), parsed)
     }
+
+    #[test]
+    fn parse_uppercase_hex_prefix() {
+        let parsed = StreamId::try_from("0Xad0100000000000000000000000000000000000009").unwrap();
+        assert_eq!(USER_METADATA_STREAM_ID_PREFIX, parsed.stream_type());
+    }
+
+    #[test]
+    fn parse_long_stream_id_in_short_form() {
+        let e = StreamId::try_from("200100000000000000000000000000000000000009").unwrap_err();
+        assert!(matches!(
+            e,
+            TownsError::InvalidStreamIdLength(CHANNEL_STREAM_ID_PREFIX, STREAM_ID_LEN, 21)
+        ));
+    }
+
+    #[test]
+    fn parse_user_stream_id_with_padding() {
+        let hex_encoded = "a801000000000000000000000000000000000000090000000000000000000000";
+        let parsed = StreamId::try_from(hex_encoded).unwrap();
+        assert!(matches!(parsed, StreamId::User(_)));
+        assert_eq!(
+            "0xa80100000000000000000000000000000000000009",
+            parsed.to_string()
+        );
+
+        let hex_encoded = "a801000000000000000000000000000000000000090000000000000000000001";
+        assert!(StreamId::try_from(hex_encoded).is_err());
+    }
+
+    #[test]
+    fn user_streams_from_addr() {
+        let addr = Address::repeat_byte(0x42);
+        for (id, prefix) in [
+            (
+                StreamId::user_stream_from_addr(&addr),
+                USER_STREAM_ID_PREFIX,
+            ),
+            (
+                StreamId::user_settings_stream_from_addr(&addr),
+                USER_SETTINGS_STREAM_ID_PREFIX,
+            ),
+            (
+                StreamId::user_inbox_stream_from_addr(&addr),
+                USER_INBOX_STREAM_ID_PREFIX,
+            ),
+            (
+                StreamId::user_metadata_key_stream_from_addr(&addr),
+                USER_METADATA_STREAM_ID_PREFIX,
+            ),
+        ] {
+            assert_eq!(prefix, id.stream_type());
+            assert_eq!(addr.as_slice(), &id.as_bytes()[1..]);
+            assert_eq!(id, StreamId::try_from(id.as_bytes()).unwrap());
+        }
+    }
+
+    fn canonical(prefix: u8, tail: &[u8; STREAM_ID_LEN - 1]) -> Vec<u8> {
+        let len = if is_short_prefix(prefix) {
+            SHORT_STREAM_ID_LEN
+        } else {
+            STREAM_ID_LEN
+        };
+        let mut raw = vec![prefix];
+        raw.extend_from_slice(&tail[..len - 1]);
+        raw
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn round_trip_canonical_form(
+            prefix in proptest::sample::select(STREAM_ID_PREFIXES.to_vec()),
+            tail in proptest::array::uniform31(proptest::num::u8::ANY),
+        ) {
+            let raw = canonical(prefix, &tail);
+            let id = StreamId::try_from(raw.as_slice()).unwrap();
+
+            proptest::prop_assert_eq!(prefix, id.stream_type());
+            proptest::prop_assert_eq!(Some(id.kind()), StreamKind::from_prefix(prefix));
+            proptest::prop_assert_eq!(id.kind().is_user(), id.user_address().is_some());
+            proptest::prop_assert_eq!(raw.as_slice(), id.as_bytes());
+            proptest::prop_assert_eq!(&raw, &Vec::<u8>::from(id));
+            proptest::prop_assert_eq!(id, StreamId::try_from(id.to_string().as_str()).unwrap());
+            proptest::prop_assert_eq!(id, StreamId::try_from(id.as_fixed_bytes32().as_slice()).unwrap());
+            proptest::prop_assert_eq!(&raw[..], &id.as_fixed_bytes32()[..raw.len()]);
+        }
+
+        #[test]
+        fn reject_unknown_prefix(
+            prefix in proptest::num::u8::ANY,
+            tail in proptest::array::uniform31(proptest::num::u8::ANY),
+        ) {
+            proptest::prop_assume!(!STREAM_ID_PREFIXES.contains(&prefix));
+            let mut raw = vec![prefix];
+            raw.extend_from_slice(&tail);
+
+            proptest::prop_assert!(matches!(
+                StreamId::try_from(raw.as_slice()),
+                Err(TownsError::UnknownStreamIdPrefix(p)) if p == prefix
+            ));
+            proptest::prop_assert!(matches!(
+                StreamId::try_from(&raw[..SHORT_STREAM_ID_LEN]),
+                Err(TownsError::UnknownStreamIdPrefix(p)) if p == prefix
+            ));
+        }
+    }
+
+    #[test]
+    fn space_and_channel_streams() {
+        let space_address = Address::repeat_byte(0x42);
+        let space = StreamId::space(&space_address);
+        assert_eq!(StreamKind::Space, space.kind());
+        assert_eq!(Some(space_address), space.space_address());
+        assert_eq!(None, space.user_address());
+
+        let channel = StreamId::channel(&space_address, [7; 11]);
+        assert_eq!(StreamKind::Channel, channel.kind());
+        assert_eq!(Some(space_address), channel.space_address());
+        assert_eq!(&[7; 11], &channel.as_bytes()[SHORT_STREAM_ID_LEN..]);
+        assert_ne!(StreamId::default_channel(&space_address), channel);
+    }
+
+    #[test]
+    fn dm_stream_matches_protocol() {
+        let a = Address::repeat_byte(0x01);
+        let b = Address::repeat_byte(0x02);
+        let dm = StreamId::dm(&a, &b);
+
+        // sha256("0x0101..01-0x0202..02") truncated to 31 bytes
+        assert_eq!(
+            "0x88714d87901fbbd79be6e97e85817b058e47b7ba7ab25a356a8eb0bc2ea30488",
+            dm.to_string()
+        );
+        assert_eq!(StreamKind::Dm, dm.kind());
+        assert_eq!(dm, StreamId::dm(&b, &a));
+        assert_ne!(dm, StreamId::dm(&a, &a));
+        assert_eq!(None, dm.user_address());
+    }
+
+    #[test]
+    fn parse_stream_kind() {
+        for kind in StreamKind::ALL {
+            assert_eq!(Some(kind), StreamKind::from_prefix(kind.prefix()));
+            assert_eq!(kind, kind.name().parse().unwrap());
+            assert_eq!(kind, kind.prefix().to_string().parse().unwrap());
+            assert_eq!(kind, format!("{:#04x}", kind.prefix()).parse().unwrap());
+        }
+        assert!("0x01".parse::<StreamKind>().is_err());
+        assert!("channels".parse::<StreamKind>().is_err());
+    }
+
+    #[test]
+    fn order_by_bytes() {
+        let space = StreamId::space(&Address::repeat_byte(0xff));
+        let channel = StreamId::default_channel(&Address::ZERO);
+        let user = StreamId::user_stream_from_addr(&Address::ZERO);
+
+        let mut ids = vec![user, channel, space];
+        ids.sort();
+        assert_eq!(vec![space, channel, user], ids);
+    }
+
+    #[test]
+    fn decode_compact_stream_ids() {
+        let user = StreamId::user_stream_from_addr(&Address::repeat_byte(0x01));
+        let channel = StreamId::default_channel(&Address::repeat_byte(0x02));
+
+        let mut buf = Vec::from(user);
+        buf.extend(Vec::from(channel));
+
+        let (decoded, read) = StreamId::decode_compact(&buf).unwrap();
+        assert_eq!((user, SHORT_STREAM_ID_LEN), (decoded, read));
+        let (decoded, read) = StreamId::decode_compact(&buf[read..]).unwrap();
+        assert_eq!((channel, STREAM_ID_LEN), (decoded, read));
+
+        assert!(matches!(
+            StreamId::decode_compact(&buf[SHORT_STREAM_ID_LEN..STREAM_ID_LEN]),
+            Err(TownsError::InvalidStreamIdLength(
+                CHANNEL_STREAM_ID_PREFIX,
+                32,
+                11
+            ))
+        ));
+        assert!(StreamId::decode_compact(&[]).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_unknown_stream_ids() {
+        let mut raw = FixedBytes::<STREAM_ID_LEN>::ZERO;
+        raw[0] = 0x12;
+        let id = StreamId::from_registry(&raw);
+
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(id, serde_json::from_str::<StreamId>(&json).unwrap());
+        let encoded = bincode::serialize(&id).unwrap();
+        assert_eq!(id, bincode::deserialize::<StreamId>(&encoded).unwrap());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_stream_ids() {
+        let user = StreamId::user_stream_from_addr(&Address::repeat_byte(0x01));
+        let json = serde_json::to_string(&user).unwrap();
+        assert_eq!(format!("\"{}\"", user), json);
+        assert_eq!(user, serde_json::from_str::<StreamId>(&json).unwrap());
+
+        let encoded = bincode::serialize(&user).unwrap();
+        assert_eq!(8 + SHORT_STREAM_ID_LEN, encoded.len());
+        assert_eq!(user, bincode::deserialize::<StreamId>(&encoded).unwrap());
+
+        assert!(serde_json::from_str::<StreamId>("\"0x20\"").is_err());
+    }
+
+    #[cfg(feature = "borsh")]
+    #[test]
+    fn borsh_stream_ids() {
+        let ids = vec![
+            StreamId::user_stream_from_addr(&Address::repeat_byte(0x01)),
+            StreamId::default_channel(&Address::repeat_byte(0x02)),
+        ];
+        let encoded = borsh::to_vec(&ids).unwrap();
+        assert_eq!(4 + SHORT_STREAM_ID_LEN + STREAM_ID_LEN, encoded.len());
+        assert_eq!(ids, borsh::from_slice::<Vec<StreamId>>(&encoded).unwrap());
+    }
+
+    #[test]
+    fn unknown_stream_ids_from_registry() {
+        let mut raw = FixedBytes::<STREAM_ID_LEN>::repeat_byte(0x01);
+        raw[0] = 0x12;
+
+        assert!(matches!(
+            StreamId::try_from(&raw),
+            Err(TownsError::UnknownStreamIdPrefix(0x12))
+        ));
+
+        let id = StreamId::from_registry(&raw);
+        assert_eq!(StreamId::Unknown(raw), id);
+        assert!(id.is_unknown());
+        assert_eq!(StreamKind::Unknown(0x12), id.kind());
+        assert_eq!("unknown(0x12)", id.kind().to_string());
+        assert_eq!(raw.as_slice(), id.as_bytes());
+        assert_eq!(None, id.user_address());
+        assert_eq!(
+            (id, STREAM_ID_LEN),
+            StreamId::decode_compact(raw.as_slice()).unwrap()
+        );
+
+        // user stream ids with invalid padding are kept as unknown too, with all 32 bytes
+        let mut raw = StreamId::user_stream_from_addr(&Address::ZERO).as_fixed_bytes32();
+        raw[31] = 0x01;
+        let id = StreamId::from_registry(&raw);
+        assert_eq!(StreamId::Unknown(raw), id);
+        assert_eq!(StreamKind::Unknown(USER_STREAM_ID_PREFIX), id.kind());
+        assert!(!id.kind().is_user());
+        assert_eq!(None, id.user_address());
+        assert_eq!(raw.as_slice(), id.as_bytes());
+        assert_eq!(format!("0x{}", hex::encode(raw)), id.to_string());
+
+        let mut compact = Vec::new();
+        id.encode_compact(&mut compact);
+        assert_eq!(STREAM_ID_LEN + 1, compact.len());
+        assert_eq!(
+            (id, STREAM_ID_LEN + 1),
+            StreamId::decode_compact(&compact).unwrap()
+        );
+
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&id).unwrap();
+            assert_eq!(format!("\"{}\"", id), json);
+            assert_eq!(id, serde_json::from_str::<StreamId>(&json).unwrap());
+            let encoded = bincode::serialize(&id).unwrap();
+            assert_eq!(id, bincode::deserialize::<StreamId>(&encoded).unwrap());
+        }
+
+        #[cfg(feature = "borsh")]
+        {
+            let encoded = borsh::to_vec(&id).unwrap();
+            assert_eq!(compact, encoded);
+            assert_eq!(id, borsh::from_slice::<StreamId>(&encoded).unwrap());
+        }
+    }
 }